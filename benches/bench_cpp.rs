@@ -6,26 +6,46 @@ use criterion::black_box;
 use criterion::Criterion;
 
 use crust::cpp;
-use std::{error, fs, path::PathBuf};
+use std::{error, fs, path::Path};
 
 fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
     // TODO: change this to iter through every c file under "test/valid/cpp"
     let input_files = &[
         "test/valid/cpp/macro_object.c",
         "test/valid/cpp/macro_object_2.c",
+        "test/valid/cpp/macro_function.c",
+        "test/valid/cpp/conditional.c",
+        "test/valid/cpp/macro_in_literal.c",
+        "test/valid/cpp/macro_recursive.c",
+        "test/valid/cpp/macro_std_example_3.c",
+        "test/valid/cpp/macro_std_example_4.c",
+        "test/valid/cpp/macro_variadic.c",
+        "test/valid/cpp/predefined.c",
+        "test/valid/cpp/line.c",
+        "test/valid/cpp/include_once.c",
+        "test/valid/cpp/directives.c",
+        "test/valid/cpp/pragma_operator.c",
+        "test/valid/cpp/bundled_headers.c",
+        "test/valid/cpp/digraphs.c",
         "test/valid/cpp/trash.c",
         "test/valid/cpp/header1.c",
         "test/valid/cpp/comment_1.c",
-        "test/valid/cpp/tri_1.c",
-        "test/valid/cpp/tri_2.c",
-        "test/valid/cpp/tri_3.c",
+        "test/valid/cpp/comment_2.c",
+        "test/valid/cpp/trigraphs/tri_1.c",
+        "test/valid/cpp/trigraphs/tri_2.c",
+        "test/valid/cpp/trigraphs/tri_3.c",
     ];
     for input_file in input_files.iter() {
         c.bench_function(&format!("cpp {}", input_file), move |b| {
             b.iter(|| {
-                cpp::cpp_driver(
+                cpp::Preprocessor::new(cpp::Options {
+                    trigraphs: true,
+                    ..cpp::Options::default()
+                })
+                .unwrap()
+                .preprocess(
                     black_box(fs::read_to_string(input_file).unwrap()),
-                    PathBuf::from(input_file),
+                    Path::new(input_file),
                 )
             })
         });
@@ -11,6 +11,9 @@ use std::{error, fs};
 fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
     let input_files = &[
         "test/valid/nested_scope_2.c",
+        "test/valid/float_constants.c",
+        "test/valid/integer_constants.c",
+        "test/valid/char_string_literals.c",
         "test/valid/consecutive_declarations.c",
         "test/valid/return_0.c",
         "test/valid/continue_empty_post.c",
@@ -6,7 +6,7 @@ use criterion::black_box;
 use criterion::Criterion;
 
 use crust::{cpp, lexer, parser};
-use std::{error, fs, path::PathBuf};
+use std::{error, fs, path::Path};
 
 fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
     let input_files = &[
@@ -143,10 +143,10 @@ fn criterion_benchmark(c: &mut Criterion) -> Result<(), Box<dyn error::Error>> {
     for input_file in input_files.iter() {
         let input_file_contents = fs::read_to_string(input_file.clone())?;
         // 1. Preprocessing
-        let contents_after_cpp =
-            cpp::cpp_driver(input_file_contents, PathBuf::from(input_file.clone()))?;
+        let tokens_after_cpp = cpp::Preprocessor::new(cpp::Options::default())?
+            .preprocess(input_file_contents, Path::new(input_file))?;
         // 2. lexing
-        let tokens = lexer::lex(&contents_after_cpp)?;
+        let tokens = lexer::lex_tokens(&tokens_after_cpp)?;
         let name = input_file.clone();
 
         c.bench_function(&format!("parse {}", input_file), move |b| {
@@ -8,7 +8,7 @@ pub enum NodeType {
     Constant(ConstantType),
     EnumerationConstant(String),
     Identifier(String),
-    STRING(String),
+    STRING(Vec<u32>, lexer::Encoding), // code units without the null character
     GenericSelection,
     GenericAssociation,
     GenericAssocList,
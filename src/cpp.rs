@@ -16,344 +16,2872 @@
 // cpp.rs: Simple c preprocessor
 // -----------------------------------------------------------------------------
 
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::{error, fs, path::Path};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, error, path::Path, str::FromStr};
 
-fn trigraph_processor(input: String) -> Result<String, String> {
-    // Trigraph:       ??(  ??)  ??<  ??>  ??=  ??/  ??'  ??!  ??-
-    // Replacement:      [    ]    {    }    #    \    ^    |    ~
-    let mut res = "".to_string();
+mod deps;
+mod expr;
+mod files;
+mod headers;
 
+pub use deps::Dependencies;
+pub use files::{FileProvider, MemoryFileSystem, RealFileSystem};
+pub use headers::BUNDLED_INCLUDE_DIR;
+
+/// The character a trigraph `??c` stands for, C11 5.2.1.1.
+fn trigraph(c: char) -> Option<char> {
+    let res = match c {
+        '(' => '[',
+        ')' => ']',
+        '<' => '{',
+        '>' => '}',
+        '=' => '#',
+        '/' => '\\',
+        '\'' => '^',
+        '!' => '|',
+        '-' => '~',
+        _ => return None,
+    };
+    Some(res)
+}
+
+/// The trigraphs in `input` as (byte offset, replacement), `???=` is `?` followed by `??=`.
+fn find_trigraphs(input: &str) -> Vec<(usize, char)> {
+    let mut res = Vec::new();
     let mut idx = 0;
-    while idx < input.len() {
-        if idx > input.len() - 3 {
-            res.push(char::from(input.as_bytes()[idx]));
-            idx = idx + 1;
-            continue;
-        }
-        let mut combine = String::new();
-        combine.push(char::from(input.as_bytes()[idx]));
-        combine.push(char::from(input.as_bytes()[idx + 1]));
-        combine.push(char::from(input.as_bytes()[idx + 2]));
-        match combine.as_ref() {
-            "??(" => {
-                res.push('[');
-                idx += 3;
-            }
-            "??)" => {
-                res.push(']');
-                idx += 3;
-            }
-            "??<" => {
-                res.push('{');
-                idx += 3;
+    while let Some(offset) = input[idx..].find("??") {
+        let start = idx + offset;
+        match input[start + 2..].chars().next().and_then(trigraph) {
+            Some(c) => {
+                res.push((start, c));
+                // the trigraph is ASCII, so it is 3 bytes long
+                idx = start + 3;
             }
-            "??>" => {
-                res.push('}');
-                idx += 3;
-            }
-            "??=" => {
-                res.push('#');
-                idx += 3;
-            }
-            "??/" => {
-                res.push('\\');
-                idx += 3;
-            }
-            "??'" => {
-                res.push('^');
-                idx += 3;
+            None => idx = start + 1,
+        }
+    }
+    res
+}
+
+/// The characters of `input` with their byte offsets.
+///
+/// The translation phases 1 to 3 work on them, so that the tokens know where they are
+/// in the source file even after the trigraphs, the line splices and the comments are gone.
+fn located_chars(input: &str) -> Vec<(char, usize)> {
+    input
+        .char_indices()
+        .map(|(offset, c)| (c, offset))
+        .collect()
+}
+
+/// Translation phase 1 for trigraphs, they are only replaced with `-trigraphs`.
+fn trigraph_processor(input: &str) -> Vec<(char, usize)> {
+    let mut trigraphs = find_trigraphs(input).into_iter().peekable();
+    let mut res = Vec::with_capacity(input.len());
+    let mut it = input.char_indices();
+    while let Some((offset, c)) = it.next() {
+        match trigraphs.peek() {
+            Some(&(start, replacement)) if start == offset => {
+                res.push((replacement, offset));
+                // skip the other two characters of the trigraph
+                it.nth(1);
+                trigraphs.next();
             }
-            "??!" => {
-                res.push('|');
-                idx += 3;
+            _ => res.push((c, offset)),
+        }
+    }
+    res
+}
+
+/// Warn about the trigraphs which are not replaced without `-trigraphs`, like gcc.
+///
+/// The comments are already removed from `src`, so the trigraphs in them are not reported.
+/// `input` is the source file which the offsets of `src` point into.
+fn warn_trigraphs(src: &[(char, usize)], input: &str, file: &Path) {
+    let mut line = 1;
+    let mut last = 0;
+    let mut idx = 0;
+    while idx + 2 < src.len() {
+        if src[idx].0 != '?' || src[idx + 1].0 != '?' || trigraph(src[idx + 2].0).is_none() {
+            idx += 1;
+            continue;
+        }
+        let offset = src[idx].1;
+        line += input[last..offset].matches('\n').count();
+        last = offset;
+        warning(&format!(
+            "{}:{}: trigraph ??{} ignored, use -trigraphs to enable",
+            file.display(),
+            line,
+            src[idx + 2].0
+        ));
+        idx += 3;
+    }
+}
+
+/// Translation phase 2: remove each backslash followed by a newline.
+///
+/// A backslash at the very end of `text`, the source file `file`, is dropped with a warning.
+fn line_concat(input: Vec<(char, usize)>, text: &str, file: &Path) -> Vec<(char, usize)> {
+    let mut res = Vec::with_capacity(input.len());
+    let mut it = input.into_iter().peekable();
+    while let Some((c, offset)) = it.next() {
+        if c == '\\' {
+            let mut ahead = it.clone();
+            if matches!(ahead.peek(), Some(('\r', _))) {
+                ahead.next();
             }
-            "??-" => {
-                res.push('~');
-                idx += 3;
+            if matches!(ahead.peek(), Some(('\n', _))) {
+                // remove this `\` and `\n`
+                ahead.next();
+                it = ahead;
+                continue;
             }
-            _ => {
-                res.push(char::from(input.as_bytes()[idx]));
-                idx += 1;
+            if ahead.peek().is_none() {
+                warning(&locate(
+                    file,
+                    text,
+                    offset,
+                    "backslash-newline at end of file",
+                ));
+                continue;
             }
         }
+        res.push((c, offset));
     }
-    return Ok(res);
+    res
 }
 
-fn line_concat(input: String) -> Result<String, String> {
-    let mut res = String::new();
-    let mut it = input.chars().peekable();
-    while let Some(&c) = it.peek() {
-        if c == '\\' {
-            it.next();
-            if let Some(&nc) = it.peek() {
-                match nc {
-                    '\n' => {
-                        // remove this `\` and `\n`
-                        // so nothing here, just skip
+/// Translation phase 3 for comments: each comment is replaced by one space character.
+///
+/// `//` and `/*` inside string literals and character constants do not start a comment.
+/// The tokens keep their line numbers, the tokenizer counts the lines in the source file.
+/// `text` is the source file `file` which the offsets point into, for the errors.
+fn remove_comment(
+    input: Vec<(char, usize)>,
+    text: &str,
+    file: &Path,
+) -> Result<Vec<(char, usize)>, String> {
+    let mut res = Vec::with_capacity(input.len());
+    // the quote character, when inside a string literal or character constant
+    let mut quote: Option<char> = None;
+    let mut it = input.into_iter().peekable();
+    while let Some((c, offset)) = it.next() {
+        if let Some(q) = quote {
+            res.push((c, offset));
+            if c == '\\' {
+                if let Some(&(n, n_offset)) = it.peek() {
+                    if n != '\n' {
+                        res.push((n, n_offset));
+                        it.next();
                     }
-                    _ => {
-                        res.push(c);
-                        res.push(nc);
+                }
+            } else if c == q || c == '\n' {
+                // an unmatched quote ends at the end of the line, the tokenizer handles it
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                quote = Some(c);
+                res.push((c, offset));
+            }
+            '/' if matches!(it.peek(), Some(('/', _))) => {
+                while it.peek().is_some_and(|&(n, _)| n != '\n') {
+                    it.next();
+                }
+                res.push((' ', offset));
+            }
+            '/' if matches!(it.peek(), Some(('*', _))) => {
+                it.next();
+                let mut closed = false;
+                while let Some((c, inner)) = it.next() {
+                    match c {
+                        '*' if matches!(it.peek(), Some(('/', _))) => {
+                            it.next();
+                            closed = true;
+                            break;
+                        }
+                        '/' if matches!(it.peek(), Some(('*', _))) => {
+                            warning(&locate(file, text, inner, "\"/*\" within comment"))
+                        }
+                        _ => {}
                     }
                 }
-            } else {
-                // no other characters, at the end of file
-                // Should give warning, but continue compile.
-                // cause crust now has no warning option, so just remove this `\`
-                break;
+                if !closed {
+                    return Err(locate(file, text, offset, "unterminated comment"));
+                }
+                res.push((' ', offset));
             }
-        } else {
-            res.push(c);
+            _ => res.push((c, offset)),
         }
-        it.next();
     }
+    Ok(res)
+}
+
+/// System directories searched after the `-isystem` ones, unless `-nostdinc` is given.
+pub const DEFAULT_SYSTEM_INCLUDE_DIRS: [&str; 4] = [
+    BUNDLED_INCLUDE_DIR,
+    "/usr/local/include",
+    "/usr/include/x86_64-linux-gnu",
+    "/usr/include",
+];
 
-    return Ok(res);
+/// Directories searched by `#include`, in the same order as gcc:
+///
+/// * `#include "file"`: the directory of the current file, `quote`, `angled`, `system`
+/// * `#include <file>`: `angled`, `system`
+#[derive(Clone, Debug, Default)]
+pub struct IncludePaths {
+    /// `-iquote` directories
+    pub quote: Vec<PathBuf>,
+    /// `-I` directories
+    pub angled: Vec<PathBuf>,
+    /// `-isystem` and the default system directories
+    pub system: Vec<PathBuf>,
 }
 
-fn remove_comment(input: String) -> Result<String, String> {
-    fn in_comment(single: bool, multi: bool) -> bool {
-        return single || multi;
+impl IncludePaths {
+    /// Find the header `name`, `current_dir` is the directory of the file which includes it.
+    ///
+    /// For `#include_next`, `after` is the index of the search directory of the including
+    /// file and only the directories after it are searched.
+    fn find(
+        &self,
+        files: &dyn FileProvider,
+        name: &str,
+        angled: bool,
+        current_dir: Option<&Path>,
+        after: Option<usize>,
+    ) -> Option<Header> {
+        let name = Path::new(name);
+        if name.is_absolute() {
+            return Some(Header {
+                path: name.to_path_buf(),
+                system: false,
+                dir: None,
+            })
+            .filter(|header| files.is_file(&header.path));
+        }
+        let mut dirs: Vec<(Option<usize>, &Path, bool)> = Vec::new();
+        if !angled && after.is_none() {
+            dirs.push((None, current_dir.unwrap_or_else(|| Path::new("")), false));
+        }
+        let search = self
+            .quote
+            .iter()
+            .map(|dir| (dir, false))
+            .chain(self.angled.iter().map(|dir| (dir, false)))
+            .chain(self.system.iter().map(|dir| (dir, true)))
+            .enumerate()
+            .map(|(idx, (dir, system))| (Some(idx), dir.as_path(), system));
+        // `<...>` skips the `-iquote` directories
+        let first = if angled { self.quote.len() } else { 0 };
+        let first = after.map_or(first, |idx| first.max(idx + 1));
+        dirs.extend(search.skip(first));
+        dirs.into_iter()
+            .map(|(dir, path, system)| Header {
+                path: path.join(name),
+                system,
+                dir,
+            })
+            .find(|header| files.is_file(&header.path))
     }
+}
+
+/// A header found by `IncludePaths::find`.
+struct Header {
+    path: PathBuf,
+    // found in a system directory
+    system: bool,
+    // index of the search directory, None for the directory of the including file
+    dir: Option<usize>,
+}
+
+/// Kind of a preprocessing token, C11 6.4
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PPTokenKind {
+    // only in `#include` directives
+    HeaderName,
+    Identifier,
+    Number,
+    CharConstant,
+    StringLiteral,
+    Punctuator,
+    // a character that matches none of the above, like `@` or an unmatched `'`
+    Other,
+    // empty macro argument, only lives during `##` processing (C11 6.10.3.3p2)
+    Placemarker,
+    // not a real token: the following tokens come from the file named by `text`,
+    // starting at `line`
+    Linemarker(FileChange),
+    // a directive kept in the output, like `#pragma`, `text` is the whole line
+    Directive,
+}
+
+/// Why a linemarker is emitted, written as the flag after the file name in
+/// `# 1 "header.h" 1`.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FileChange {
+    /// start of the main file or `#line`, no flag
+    Rename,
+    /// flag 1, start of an included file
+    Enter,
+    /// flag 2, return to the including file
+    Return,
+}
+
+/// A preprocessing token, the output of the preprocessor is a list of them.
+#[derive(PartialEq, Clone, Debug)]
+pub struct PPToken {
+    pub kind: PPTokenKind,
+    pub text: String,
+    /// true if the token is preceded by white space
+    pub space: bool,
+    /// true if the token is the first one of a line
+    pub bol: bool,
+    /// line number in the source file, tokens from a macro expansion get the location
+    /// of the macro name
+    pub line: usize,
+    /// byte offset of the token in the source file
+    pub offset: usize,
+    /// column of the token in its line, counted in bytes from 1 like gcc
+    pub column: usize,
+    // names of the macros that must not be expanded from this token, C11 6.10.3.4p2
+    hide_set: HashSet<String>,
+    /// the macro expansion which produced the token
+    pub expansion: Option<Arc<Expansion>>,
+}
+
+/// A macro expansion, the provenance of the tokens it produces.
+#[derive(PartialEq, Debug)]
+pub struct Expansion {
+    /// name of the expanded macro
+    pub name: String,
+    /// where the macro is defined
+    pub file: String,
+    pub line: usize,
+    /// the expansion which produced the macro name, if it comes from another macro
+    pub parent: Option<Arc<Expansion>>,
+}
+
+/// The chain of macro expansions `expansion`, innermost first, as one note per line. Each
+/// line starts with a newline, so the notes are appended to the message of a diagnostic.
+pub fn expansion_notes(mut expansion: Option<&Expansion>) -> String {
     let mut res = String::new();
+    while let Some(e) = expansion {
+        res.push_str(&format!(
+            "\nnote: in expansion of macro '{}' defined at {}:{}",
+            e.name, e.file, e.line
+        ));
+        expansion = e.parent.as_deref();
+    }
+    res
+}
 
-    let mut idx = 0;
-    let mut single_line_in_comment = false;
-    let mut multi_line_in_comment = false;
-    while idx < input.len() {
-        if idx == input.len() - 1 {
-            if single_line_in_comment || multi_line_in_comment {
-                // skip
-                break;
-            } else {
-                res.push(char::from(input.as_bytes()[idx]));
-                break;
+impl PPToken {
+    fn new(kind: PPTokenKind, text: &str, space: bool) -> PPToken {
+        PPToken {
+            kind,
+            text: text.to_string(),
+            space,
+            bol: false,
+            line: 0,
+            offset: 0,
+            column: 0,
+            hide_set: HashSet::new(),
+            expansion: None,
+        }
+    }
+
+    fn linemarker(file: &str, line: usize, change: FileChange) -> PPToken {
+        let mut tok = PPToken::new(PPTokenKind::Linemarker(change), file, false);
+        tok.line = line;
+        tok
+    }
+
+    /// Give the token the location of `tok`.
+    fn locate_at(&mut self, tok: &PPToken) {
+        self.line = tok.line;
+        self.offset = tok.offset;
+        self.column = tok.column;
+    }
+
+    /// Whether the token is the punctuator `p`, a digraph is the punctuator it stands for.
+    fn is_punct(&self, p: &str) -> bool {
+        self.kind == PPTokenKind::Punctuator && digraph(&self.text).unwrap_or(&self.text) == p
+    }
+}
+
+/// A macro recorded by `#define`.
+#[derive(PartialEq, Clone, Debug)]
+struct Macro {
+    // None for object-like macros, parameter names for function-like macros
+    params: Option<Vec<String>>,
+    // the last parameter is `...` (named `__VA_ARGS__`) or a GNU named variable argument `args...`
+    variadic: bool,
+    body: Vec<PPToken>,
+    // set for the predefined macros whose value depends on where they are used
+    dynamic: Option<DynamicMacro>,
+    // where the macro is defined
+    file: String,
+    line: usize,
+}
+
+/// Predefined macros that are replaced by the current location or state, C11 6.10.8.1
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum DynamicMacro {
+    File,
+    Line,
+    // GNU extension, expands to 0, 1, 2, ... at each use
+    Counter,
+}
+
+impl DynamicMacro {
+    /// The replacement of the macro `tok` used in the source file `file`,
+    /// `counter` is the value of the next `__COUNTER__`.
+    fn replace(self, tok: &PPToken, file: &str, counter: &Cell<usize>) -> PPToken {
+        let (kind, text) = match self {
+            DynamicMacro::File => (PPTokenKind::StringLiteral, quote(file)),
+            DynamicMacro::Line => (PPTokenKind::Number, tok.line.to_string()),
+            DynamicMacro::Counter => {
+                let value = counter.get();
+                counter.set(value + 1);
+                (PPTokenKind::Number, value.to_string())
             }
+        };
+        PPToken::new(kind, &text, false)
+    }
+}
+
+// punctuators of C11 6.4.6, longest first so that the tokenizer can take the longest match
+const PUNCTUATORS: [&str; 53] = [
+    "%:%:", "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##", "<:", ":>", "<%", "%>", "%:", "[", "]",
+    "(", ")", "{", "}", ".", "&", "*", "+", "-", "~", "!", "/", "%", "<", ">", "^", "|", "?", ":",
+    ";", "=", ",",
+];
+
+/// The punctuator a digraph stands for, C11 6.4.6p3.
+pub fn digraph(text: &str) -> Option<&'static str> {
+    let res = match text {
+        "<:" => "[",
+        ":>" => "]",
+        "<%" => "{",
+        "%>" => "}",
+        "%:" => "#",
+        "%:%:" => "##",
+        _ => return None,
+    };
+    Some(res)
+}
+
+fn is_id_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_id_continue(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Length of the header name starting at `idx`, if there is one.
+fn scan_header_name(chars: &[char], idx: usize) -> Option<usize> {
+    let close = match chars[idx] {
+        '<' => '>',
+        '"' => '"',
+        _ => return None,
+    };
+    let len = chars[idx + 1..]
+        .iter()
+        .position(|&c| c == close || c == '\n')?;
+    if chars[idx + 1 + len] == close {
+        Some(len + 2)
+    } else {
+        None
+    }
+}
+
+/// The length of the string literal or character constant which starts with the quote
+/// at `idx`, None if it is not closed on the same line.
+fn scan_quoted(chars: &[char], idx: usize) -> Option<usize> {
+    let quote = chars[idx];
+    let mut end = idx + 1;
+    while end < chars.len() && chars[end] != quote && chars[end] != '\n' {
+        if chars[end] == '\\' {
+            end += 1;
         }
-        let b1 = char::from(input.as_bytes()[idx]);
-        let b2 = char::from(input.as_bytes()[idx + 1]);
+        end += 1;
+    }
+    if end < chars.len() && chars[end] == quote {
+        Some(end + 1 - idx)
+    } else {
+        None
+    }
+}
 
-        let mut combine = String::new();
-        combine.push(b1);
-        combine.push(b2);
-        match combine.as_ref() {
-            "//" => {
-                if !in_comment(single_line_in_comment, multi_line_in_comment) {
-                    single_line_in_comment = true;
-                }
-                idx = idx + 2;
+/// Split the text into preprocessing tokens (C11 6.4), the input should have no comments.
+///
+/// Header names are only recognized after `#include`, as C11 6.4p4 requires,
+/// and in `__has_include`.
+pub fn tokenize(input: &str) -> Vec<PPToken> {
+    tokenize_located(&located_chars(input), input)
+}
+
+/// Split the characters into preprocessing tokens, `text` is the source file which
+/// the offsets of the characters point into.
+fn tokenize_located(src: &[(char, usize)], text: &str) -> Vec<PPToken> {
+    let chars: Vec<char> = src.iter().map(|&(c, _)| c).collect();
+    let mut res: Vec<PPToken> = Vec::new();
+    // the line of the last token in `text`, the byte offsets of its start and of the
+    // last token; the lines are counted in `text` so that a token after a line splice
+    // or a comment with newlines gets its physical line
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut last = 0;
+    let mut space = false;
+    let mut bol = true;
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        let start = idx;
+        let after_include = !bol
+            && res.len() >= 2
+            && ((res[res.len() - 2].bol
+                && res[res.len() - 2].is_punct("#")
+                && ["include", "include_next"].contains(&res[res.len() - 1].text.as_str()))
+                || (HAS_INCLUDE.contains(&res[res.len() - 2].text.as_str())
+                    && res[res.len() - 1].is_punct("(")));
+        let kind = match c {
+            '\n' => {
+                bol = true;
+                space = false;
+                idx += 1;
+                continue;
+            }
+            ' ' | '\t' | '\r' | '\x0b' | '\x0c' => {
+                space = true;
+                idx += 1;
+                continue;
+            }
+            '<' | '"' if after_include && scan_header_name(&chars, idx).is_some() => {
+                idx += scan_header_name(&chars, idx).unwrap();
+                PPTokenKind::HeaderName
             }
-            "/*" => {
-                if !in_comment(single_line_in_comment, multi_line_in_comment) {
-                    multi_line_in_comment = true;
+            _ if is_id_start(c) => {
+                while idx < chars.len() && is_id_continue(chars[idx]) {
+                    idx += 1;
+                }
+                // an encoding prefix belongs to the string literal or character
+                // constant right after it, C11 6.4.4.4 and 6.4.5
+                let prefix: String = chars[start..idx].iter().collect();
+                let quoted = match (prefix.as_str(), chars.get(idx)) {
+                    ("L", Some('"')) | ("u", Some('"')) | ("U", Some('"')) | ("u8", Some('"')) => {
+                        scan_quoted(&chars, idx).map(|len| (len, PPTokenKind::StringLiteral))
+                    }
+                    ("L", Some('\'')) | ("u", Some('\'')) | ("U", Some('\'')) => {
+                        scan_quoted(&chars, idx).map(|len| (len, PPTokenKind::CharConstant))
+                    }
+                    _ => None,
+                };
+                match quoted {
+                    Some((len, kind)) => {
+                        idx += len;
+                        kind
+                    }
+                    None => PPTokenKind::Identifier,
                 }
-                idx = idx + 2;
             }
-            "*/" => {
-                if multi_line_in_comment {
-                    multi_line_in_comment = false;
-                    idx = idx + 2;
-                } else {
-                    res.push(b1);
-                    idx = idx + 1;
+            _ if c.is_ascii_digit()
+                || (c == '.' && idx + 1 < chars.len() && chars[idx + 1].is_ascii_digit()) =>
+            {
+                // pp-number: . digit, e+, E-, p+, P- and identifier characters
+                idx += 1;
+                while idx < chars.len() {
+                    let n = chars[idx];
+                    if "eEpP".contains(n)
+                        && idx + 1 < chars.len()
+                        && (chars[idx + 1] == '+' || chars[idx + 1] == '-')
+                    {
+                        idx += 2;
+                    } else if is_id_continue(n) || n == '.' {
+                        idx += 1;
+                    } else {
+                        break;
+                    }
                 }
+                PPTokenKind::Number
             }
-            _ => {
-                if b1 == '\n' && single_line_in_comment {
-                    single_line_in_comment = false;
-                    idx = idx + 1;
-                } else {
-                    if in_comment(single_line_in_comment, multi_line_in_comment) {
-                        idx = idx + 1;
+            '"' | '\'' => match scan_quoted(&chars, idx) {
+                Some(len) => {
+                    idx += len;
+                    if c == '"' {
+                        PPTokenKind::StringLiteral
                     } else {
-                        res.push(b1);
-                        idx = idx + 1;
+                        PPTokenKind::CharConstant
+                    }
+                }
+                None => {
+                    // unmatched quote is a single `other` token, the lexer complains later
+                    idx += 1;
+                    PPTokenKind::Other
+                }
+            },
+            _ => {
+                let rest: String = chars[idx..chars.len().min(idx + 4)].iter().collect();
+                match PUNCTUATORS.iter().find(|p| rest.starts_with(*p)) {
+                    Some(p) => {
+                        idx += p.len();
+                        PPTokenKind::Punctuator
+                    }
+                    None if c == '#' => {
+                        idx += 1;
+                        PPTokenKind::Punctuator
+                    }
+                    None => {
+                        idx += 1;
+                        PPTokenKind::Other
                     }
                 }
             }
+        };
+        let mut tok = PPToken::new(kind, &chars[start..idx].iter().collect::<String>(), space);
+        tok.bol = bol;
+        tok.offset = src[start].1;
+        let skipped = &text[last..tok.offset];
+        if let Some(newline) = skipped.rfind('\n') {
+            line += skipped.matches('\n').count();
+            line_start = last + newline + 1;
         }
+        last = tok.offset;
+        tok.line = line;
+        tok.column = tok.offset - line_start + 1;
+        res.push(tok);
+        space = false;
+        bol = false;
     }
-    return Ok(res);
+    res
 }
 
-fn include_headers(input: String, parent: Option<&Path>) -> Result<String, Box<dyn error::Error>> {
-    // TODO: now only support "header.h", system header file not supported now
-    //       should add system header support when the macro expension was finished and
-    //       other directives are supported.
-    fn include_file_path(relational_name: &str) -> Result<&Path, String> {
-        if char::from(relational_name.as_bytes()[0]) == '<' {
-            return Err("Crust do not support system header now".to_string());
+/// Turn the tokens back into source text for `-E`, white space is folded into single spaces.
+///
+/// With `linemarkers` the output keeps the line numbers of the source files and tells
+/// where the lines come from with GCC style linemarkers, like `# 1 "header.h" 1`.
+pub fn detokenize(toks: &[PPToken], linemarkers: bool) -> String {
+    let mut res = String::new();
+    let mut prev: Option<&PPToken> = None;
+    let mut file = "";
+    // source line number of the next output line
+    let mut next_line = 1;
+    for tok in toks {
+        if let PPTokenKind::Linemarker(change) = tok.kind {
+            if linemarkers {
+                if !res.is_empty() {
+                    res.push('\n');
+                }
+                res.push_str(&linemarker(tok.line, &tok.text, change));
+                file = &tok.text;
+                next_line = tok.line;
+                prev = None;
+            }
+            continue;
+        }
+        if linemarkers && (tok.bol || prev.is_none()) && !res.is_empty() {
+            // short gaps are filled with empty lines, long ones with a linemarker
+            if tok.line > next_line + 8 {
+                res.push('\n');
+                res.push_str(&linemarker(tok.line, file, FileChange::Rename));
+                res.push('\n');
+            } else {
+                res.push_str(&"\n".repeat(tok.line.saturating_sub(next_line) + 1));
+            }
+            next_line = tok.line.max(next_line) + 1;
+        } else if let Some(prev) = prev {
+            if tok.bol {
+                res.push('\n');
+            } else if tok.space || avoid_paste(prev, tok) {
+                // tokens which come from different places must not be glued together
+                res.push(' ');
+            }
         }
-        let name = Path::new(&relational_name[1..(relational_name.len() - 1)]);
-        return Ok(name);
+        res.push_str(&tok.text);
+        prev = Some(tok);
     }
+    if !res.is_empty() {
+        res.push('\n');
+    }
+    res
+}
 
-    let mut res = String::new();
+/// Whether the adjacent tokens `prev` and `tok` would be read back as other tokens, or as
+/// the start of a comment, if they were written without a space, like gcc's `avoid_paste`.
+fn avoid_paste(prev: &PPToken, tok: &PPToken) -> bool {
+    use PPTokenKind::{CharConstant, Identifier, Number, Other, Punctuator, StringLiteral};
 
-    for line in input.lines() {
-        if line.is_empty() {
-            // empty line
-            res.push_str("\n");
-            continue;
+    let c = match tok.text.chars().next() {
+        Some(c) => c,
+        None => return false,
+    };
+    match prev.kind {
+        Punctuator => {
+            // the operators which form another operator with a following `=`
+            const TAKES_EQ: [&str; 14] = [
+                "=", "!", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>",
+            ];
+            if c == '=' && TAKES_EQ.contains(&prev.text.as_str()) {
+                return true;
+            }
+            match prev.text.as_str() {
+                ">" => c == '>',
+                // `<:` and `<%` are digraphs
+                "<" => c == '<' || c == ':' || c == '%',
+                "+" => c == '+',
+                "-" => c == '-' || c == '>',
+                // `//` and `/*` start a comment
+                "/" => c == '/' || c == '*',
+                "%" => c == ':' || c == '>',
+                "&" => c == '&',
+                "|" => c == '|',
+                ":" => c == '>',
+                "." => c == '.' || tok.kind == Number,
+                "#" | "%:" => c == '#' || c == '%',
+                _ => false,
+            }
         }
-        match char::from(line.trim_start().as_bytes()[0]) {
-            '#' => {
-                let a: Vec<&str> = line.split_whitespace().collect();
-                let directive = *a.get(0).unwrap();
-                match directive {
-                    "#include" => {
-                        let file_name = include_file_path(*a.get(1).unwrap())?;
-                        let full_relational_path = file_name;
-
-                        match parent {
-                            None => {
-                                let header_contents = fs::read_to_string(full_relational_path)?;
-                                res.push_str(include_headers(header_contents, None)?.as_ref());
-                            }
-                            Some(p_dir) => {
-                                let full_relational_path = p_dir.join(file_name);
-                                let header_contents = fs::read_to_string(full_relational_path)?;
-                                res.push_str(include_headers(header_contents, parent)?.as_ref());
-                            }
-                        }
-                    }
-                    _ => {
-                        // just leave other directives to be handled by the directive_handler
-                        res.push_str(line);
-                        res.push_str("\n");
+        // a pp-number continues with letters, digits and `.`, and with the sign after
+        // the exponent
+        Number => {
+            let exponent = prev.text.ends_with(|e| "eEpP".contains(e));
+            matches!(tok.kind, Identifier | Number | CharConstant)
+                || c == '.'
+                || (exponent && (c == '+' || c == '-'))
+        }
+        // also the encoding prefixes of the literals, like `L'a'`
+        Identifier => {
+            matches!(tok.kind, Identifier | CharConstant | StringLiteral)
+                || (tok.kind == Number && (c.is_ascii_alphanumeric() || c == '_'))
+        }
+        // a universal character name
+        Other => prev.text == "\\" && tok.kind == Identifier,
+        _ => false,
+    }
+}
+
+/// A linemarker line of the `-E` output.
+fn linemarker(line: usize, file: &str, change: FileChange) -> String {
+    let flag = match change {
+        FileChange::Rename => "",
+        FileChange::Enter => " 1",
+        FileChange::Return => " 2",
+    };
+    format!("# {} {}{}", line, quote(file), flag)
+}
+
+/// Make a string literal of `s`.
+fn quote(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push('"');
+    res
+}
+
+/// `#` operator, C11 6.10.3.2
+///
+/// The result must be a valid string literal, which a `\` at the end of `arg` breaks.
+fn stringize(arg: &[PPToken]) -> Result<PPToken, String> {
+    let mut s = String::from("\"");
+    for (idx, tok) in arg.iter().enumerate() {
+        if idx != 0 && tok.space {
+            s.push(' ');
+        }
+        match tok.kind {
+            PPTokenKind::StringLiteral | PPTokenKind::CharConstant => {
+                for c in tok.text.chars() {
+                    if c == '"' || c == '\\' {
+                        s.push('\\');
                     }
+                    s.push(c);
                 }
             }
-            _ => {
-                res.push_str(line);
-                res.push_str("\n");
-            }
+            _ => s.push_str(&tok.text),
         }
     }
+    if (s.len() - s.trim_end_matches('\\').len()) % 2 == 1 {
+        return Err("invalid string literal: trailing `\\`".to_string());
+    }
+    s.push('"');
+    Ok(PPToken::new(PPTokenKind::StringLiteral, &s, false))
+}
 
-    return Ok(res);
+/// `##` operator, C11 6.10.3.3
+fn paste(lhs: &PPToken, rhs: &PPToken) -> Result<PPToken, String> {
+    if lhs.kind == PPTokenKind::Placemarker {
+        return Ok(PPToken::new(rhs.kind, &rhs.text, lhs.space));
+    }
+    if rhs.kind == PPTokenKind::Placemarker {
+        return Ok(lhs.clone());
+    }
+    let text = format!("{}{}", lhs.text, rhs.text);
+    let mut toks = tokenize(&text);
+    if toks.len() != 1 || toks[0].kind == PPTokenKind::Other {
+        return Err(format!(
+            "pasting \"{}\" and \"{}\" does not give a valid preprocessing token",
+            lhs.text, rhs.text
+        ));
+    }
+    let mut res = toks.remove(0);
+    res.space = lhs.space;
+    res.bol = lhs.bol;
+    res.hide_set = lhs.hide_set.intersection(&rhs.hide_set).cloned().collect();
+    Ok(res)
 }
 
-use lazy_static;
-use std::collections::HashMap;
-use std::sync::Mutex;
+// arguments of a macro invocation and the closing `)`
+type MacroArgs = (Vec<Vec<PPToken>>, PPToken);
 
-lazy_static::lazy_static! {
-    static ref DEFINE_OBJ: Mutex<HashMap<String, String>> = {
-        let m = HashMap::new();
-        Mutex::new(m)
-    };
+/// Collect the arguments of a function-like macro invocation from `input`, which holds
+/// the tokens after the macro name in reverse order.
+/// Return None if the name is not followed by `(`.
+fn collect_args(
+    input: &mut Vec<PPToken>,
+    m: &Macro,
+    name: &str,
+) -> Result<Option<MacroArgs>, String> {
+    if !input.last().is_some_and(|tok| tok.is_punct("(")) {
+        return Ok(None);
+    }
+    input.pop();
+    let nparams = m.params.as_ref().map_or(0, |params| params.len());
+
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+    while let Some(mut tok) = input.pop() {
+        if depth == 0 && tok.is_punct(")") {
+            return Ok(Some((args, tok)));
+        }
+        // the variable argument takes all the remaining arguments, commas included
+        if depth == 0 && tok.is_punct(",") && !(m.variadic && args.len() == nparams) {
+            args.push(Vec::new());
+            continue;
+        }
+        if tok.is_punct("(") {
+            depth += 1;
+        } else if tok.is_punct(")") {
+            depth -= 1;
+        }
+        // newlines inside the invocation are just white space
+        tok.space |= tok.bol;
+        tok.bol = false;
+        args.last_mut().unwrap().push(tok);
+    }
+    Err(format!(
+        "unterminated argument list invoking macro \"{}\"",
+        name
+    ))
 }
 
-fn replace(input: String) -> String {
-    // This is a function, which replace the string if contains defined identifier.
-    let mut it = input.chars().peekable();
-    let mut res = String::new();
-    while let Some(&c) = it.peek() {
-        match c {
-            'a'...'z' | 'A'...'Z' | '_' => {
-                it.next();
-                let mut id = String::new();
-                id.push(c);
-                while let Some(&tmp) = it.peek() {
-                    match tmp {
-                        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => {
-                            id.push(tmp);
-                            it.next();
-                        }
-                        _ => {
-                            break;
-                        }
-                    }
-                }
-                let mut get_val = String::new();
-                match DEFINE_OBJ.lock().unwrap().get(&id) {
-                    Some(s) => {
-                        // XXX: I use this method to get rid of deadlock.
-                        //      cause call replace function in this scope will cause the
-                        //      child function to lock the DEFINE_OBJ, which is locked here.
-                        get_val.push_str(s);
-                    }
-                    None => {
-                        res.push_str(&id);
+/// Replace each `__VA_OPT__ ( content )` in the replacement list with the content if
+/// the variable argument has tokens after expansion, otherwise with a placemarker.
+fn replace_va_opt(body: &[PPToken], va_empty: bool) -> Vec<PPToken> {
+    let mut res = Vec::new();
+    let mut idx = 0;
+    while idx < body.len() {
+        let tok = &body[idx];
+        idx += 1;
+        if tok.kind != PPTokenKind::Identifier || tok.text != "__VA_OPT__" {
+            res.push(tok.clone());
+            continue;
+        }
+        // the definition was checked that the parentheses are balanced
+        let start = idx + 1;
+        let mut depth = 0;
+        while !(depth == 1 && body[idx].is_punct(")")) {
+            if body[idx].is_punct("(") {
+                depth += 1;
+            } else if body[idx].is_punct(")") {
+                depth -= 1;
+            }
+            idx += 1;
+        }
+        let content = &body[start..idx];
+        idx += 1;
+        if va_empty || content.is_empty() {
+            res.push(PPToken::new(PPTokenKind::Placemarker, "", tok.space));
+        } else {
+            let first = res.len();
+            res.extend_from_slice(content);
+            res[first].space = tok.space;
+        }
+    }
+    res
+}
+
+/// Check the number of arguments of an invocation of the function-like macro `m`.
+fn check_arg_count(m: &Macro, params: &[String], count: usize, name: &str) -> Result<(), String> {
+    if m.variadic && count + 1 < params.len() {
+        return Err(format!(
+            "macro \"{}\" requires at least {} arguments, but only {} given",
+            name,
+            params.len() - 1,
+            count
+        ));
+    }
+    if !m.variadic && count < params.len() {
+        return Err(format!(
+            "macro \"{}\" requires {} arguments, but only {} given",
+            name,
+            params.len(),
+            count
+        ));
+    }
+    if count > params.len() {
+        return Err(format!(
+            "macro \"{}\" passed {} arguments, but takes just {}",
+            name,
+            count,
+            params.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Check the uses of `__VA_ARGS__` and `__VA_OPT__` in a replacement list, they are only
+/// allowed in a variadic macro, C11 6.10.3p5.
+fn check_va_args(body: &[PPToken], params: &[String], variadic: bool) -> Result<(), String> {
+    let mut idx = 0;
+    // index of the `)` that closes the current `__VA_OPT__`
+    let mut va_opt_end = None;
+    while idx < body.len() {
+        let tok = &body[idx];
+        idx += 1;
+        if tok.kind != PPTokenKind::Identifier {
+            continue;
+        }
+        if tok.text == "__VA_ARGS__" && !params.iter().any(|p| p == "__VA_ARGS__") {
+            return Err(
+                "__VA_ARGS__ can only appear in the expansion of a C99 variadic macro".to_string(),
+            );
+        }
+        if tok.text != "__VA_OPT__" {
+            continue;
+        }
+        if !variadic {
+            return Err(
+                "__VA_OPT__ can only appear in the expansion of a variadic macro".to_string(),
+            );
+        }
+        if va_opt_end.is_some_and(|end| idx < end) {
+            return Err("__VA_OPT__ may not appear in a __VA_OPT__".to_string());
+        }
+        if !body.get(idx).is_some_and(|t| t.is_punct("(")) {
+            return Err("__VA_OPT__ must be followed by an open parenthesis".to_string());
+        }
+        let mut depth = 0;
+        let mut end = idx;
+        loop {
+            match body.get(end) {
+                Some(t) if t.is_punct("(") => depth += 1,
+                Some(t) if t.is_punct(")") => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
                     }
                 }
-                // after the lock was released, can lock in child replace function
-                res.push_str(&replace(get_val));
-            }
-            _ => {
-                res.push(c);
-                it.next();
+                Some(_) => {}
+                None => return Err("unterminated __VA_OPT__".to_string()),
             }
+            end += 1;
+        }
+        let content = &body[idx + 1..end];
+        if content.first().is_some_and(|t| t.is_punct("##"))
+            || content.last().is_some_and(|t| t.is_punct("##"))
+        {
+            return Err("'##' cannot appear at either end of __VA_OPT__".to_string());
         }
+        va_opt_end = Some(end);
     }
-    return res;
+    Ok(())
 }
 
-fn directive_handler(input: String) -> Result<String, Box<dyn error::Error>> {
-    // TODO: now only support #define directive
-    let mut res = String::new();
+/// The macro name of `#define` or `#undef`.
+fn macro_name(directive: &str, toks: &[PPToken]) -> Result<String, String> {
+    match toks.first() {
+        Some(tok) if tok.kind == PPTokenKind::Identifier && tok.text == "defined" => {
+            Err("\"defined\" cannot be used as a macro name".to_string())
+        }
+        Some(tok) if tok.kind == PPTokenKind::Identifier => Ok(tok.text.clone()),
+        Some(tok) => Err(format!(
+            "macro names must be identifiers, found `{}`",
+            tok.text
+        )),
+        None => Err(format!("no macro name given in #{} directive", directive)),
+    }
+}
 
-    for line in input.lines() {
-        if line.trim_start().is_empty() {
-            // empty line
-            res.push_str("\n");
-            continue;
+/// Two definitions of a macro are the same if they have the same parameters and
+/// replacement lists with the same white space separation, C11 6.10.3p2.
+fn same_definition(a: &Macro, b: &Macro) -> bool {
+    a.params == b.params
+        && a.variadic == b.variadic
+        && a.dynamic == b.dynamic
+        && a.body.len() == b.body.len()
+        && a.body
+            .iter()
+            .zip(b.body.iter())
+            .all(|(x, y)| x.text == y.text && x.space == y.space)
+}
+
+/// The `#define` directive of the macro, as written by `-dM` and `-dD`.
+fn macro_definition(name: &str, m: &Macro) -> String {
+    let mut res = format!("#define {}", name);
+    if let Some(params) = &m.params {
+        let mut params = params.clone();
+        if m.variadic {
+            let last = params.pop().unwrap_or_default();
+            params.push(match last.as_str() {
+                "__VA_ARGS__" => "...".to_string(),
+                _ => format!("{}...", last),
+            });
         }
-        match char::from(line.trim_start().as_bytes()[0]) {
-            '#' => {
-                let a: Vec<&str> = line.split_whitespace().collect();
-                let directive = *a.get(0).unwrap();
-                match directive {
-                    "#define" => {
-                        // TODO: now only support object-like macros
-                        //       macro names must identifier
-                        // remove this line and handle #define
-                        let name = String::from(*a.get(1).unwrap());
-                        let mut idx = 2;
-                        let mut replace_str = "".to_string();
-                        // cause split_whitespace() split the replace string apart, now need to combine them
-                        while idx < a.len() {
-                            replace_str.push_str(*a.get(idx).unwrap());
+        res.push_str(&format!("({})", params.join(",")));
+    }
+    let body = detokenize(&m.body, false);
+    if !body.trim_end().is_empty() {
+        res.push(' ');
+        res.push_str(body.trim_end());
+    }
+    res
+}
+
+/// Parse the tokens after `#define` into the macro name and definition,
+/// `file` is the name of the file with the `#define`.
+fn parse_define(toks: &[PPToken], file: &str) -> Result<(String, Macro), String> {
+    let name = macro_name("define", toks)?;
+
+    let mut idx = 1;
+    let mut params = None;
+    let mut variadic = false;
+    // a function-like macro has `(` right after the name, without white space
+    if idx < toks.len() && toks[idx].is_punct("(") && !toks[idx].space {
+        let mut names: Vec<String> = Vec::new();
+        idx += 1;
+        loop {
+            let tok = match toks.get(idx) {
+                Some(tok) => tok,
+                None => {
+                    return Err(format!(
+                        "missing ')' in macro parameter list of \"{}\"",
+                        name
+                    ))
+                }
+            };
+            if names.is_empty() && tok.is_punct(")") {
+                idx += 1;
+                break;
+            }
+            if tok.is_punct("...") {
+                variadic = true;
+                names.push("__VA_ARGS__".to_string());
+                idx += 1;
+                match toks.get(idx) {
+                    Some(tok) if tok.is_punct(")") => {
+                        idx += 1;
+                        break;
+                    }
+                    _ => return Err("missing ')' after \"...\"".to_string()),
+                }
+            }
+            if tok.kind == PPTokenKind::Identifier && tok.text == "__VA_ARGS__" {
+                return Err("__VA_ARGS__ can not be used as a parameter name".to_string());
+            }
+            if tok.kind != PPTokenKind::Identifier {
+                return Err(format!(
+                    "expected parameter name in macro \"{}\", found `{}`",
+                    name, tok.text
+                ));
+            }
+            if names.contains(&tok.text) {
+                return Err(format!(
+                    "duplicate macro parameter \"{}\" in macro \"{}\"",
+                    tok.text, name
+                ));
+            }
+            names.push(tok.text.clone());
+            idx += 1;
+            match toks.get(idx) {
+                Some(tok) if tok.is_punct(",") => idx += 1,
+                Some(tok) if tok.is_punct(")") => {
+                    idx += 1;
+                    break;
+                }
+                // GNU named variable argument, `args...`
+                Some(tok) if tok.is_punct("...") => {
+                    variadic = true;
+                    idx += 1;
+                    match toks.get(idx) {
+                        Some(tok) if tok.is_punct(")") => {
                             idx += 1;
-                            if idx != a.len() {
-                                replace_str = replace_str + " ";
-                            }
+                            break;
                         }
-                        let replace_str = replace(replace_str);
-                        DEFINE_OBJ.lock().unwrap().insert(name, replace_str);
-                    }
-                    _ => {
-                        res.push_str(line);
-                        res.push_str("\n");
+                        _ => return Err("missing ')' after \"...\"".to_string()),
                     }
                 }
+                _ => {
+                    return Err(format!(
+                        "missing ')' in macro parameter list of \"{}\"",
+                        name
+                    ))
+                }
             }
-            _ => {
-                // not directive starting sentence, so replace the token if it's defined before.
-                // check every identifier name
-                res.push_str(&replace(line.to_string()));
-                res.push('\n');
+        }
+        params = Some(names);
+    }
+
+    let mut body: Vec<PPToken> = toks[idx..].to_vec();
+    if let Some(first) = body.first_mut() {
+        first.space = false;
+    }
+    if body.first().is_some_and(|t| t.is_punct("##"))
+        || body.last().is_some_and(|t| t.is_punct("##"))
+    {
+        return Err("'##' cannot appear at either end of a macro expansion".to_string());
+    }
+    if let Some(names) = &params {
+        for (i, tok) in body.iter().enumerate() {
+            if tok.is_punct("#") {
+                match body.get(i + 1) {
+                    Some(next) if names.contains(&next.text) => {}
+                    _ => return Err("'#' is not followed by a macro parameter".to_string()),
+                }
             }
         }
     }
-    return Ok(res);
+    check_va_args(&body, params.as_deref().unwrap_or(&[]), variadic)?;
+
+    Ok((
+        name,
+        Macro {
+            params,
+            variadic,
+            body,
+            dynamic: None,
+            file: file.to_string(),
+            line: toks[0].line,
+        },
+    ))
 }
 
-pub fn cpp_driver(input: String, path: PathBuf) -> Result<String, Box<dyn error::Error>> {
-    let parent = path.parent();
-    // include the header files in the source file
-    let after_cpp_str = include_headers(input, parent)?;
-    // first translate trigraph into chars
-    let after_cpp_str = trigraph_processor(after_cpp_str)?;
-    // concatenate lines
-    let after_cpp_str = line_concat(after_cpp_str)?;
-    // remove comment
-    let after_cpp_str = remove_comment(after_cpp_str)?;
-    // directives handler
-    let after_cpp_str = directive_handler(after_cpp_str)?;
+fn warning(msg: &str) {
+    eprintln!("warning: {}", msg);
+}
+
+/// Prefix `msg` with the location of `tok` in the file `file`, like the errors of the parser,
+/// and follow it with the macro expansions which produced `tok`.
+fn error_at(file: &str, tok: &PPToken, msg: &str) -> String {
+    format!(
+        "{}:{}:{}: {}{}",
+        file,
+        tok.line,
+        tok.column,
+        msg,
+        expansion_notes(tok.expansion.as_deref())
+    )
+}
+
+/// The line and the column of the byte `offset` in `text`, both counted from 1.
+fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (before.matches('\n').count() + 1, offset - line_start + 1)
+}
+
+/// Prefix `msg` with the location of the byte `offset` in `text`, the contents of `file`.
+fn locate(file: &Path, text: &str, offset: usize, msg: &str) -> String {
+    let (line, column) = line_and_column(text, offset);
+    format!("{}:{}:{}: {}", file.display(), line, column, msg)
+}
+
+/// State of one `#if` ... `#endif` block.
+struct Cond {
+    // the name of the directive which opened the block, for diagnostics
+    tok: PPToken,
+    // whether the enclosing block is being processed
+    parent_active: bool,
+    // whether the current group is being processed
+    active: bool,
+    // whether one of the groups has already been taken
+    taken: bool,
+    seen_else: bool,
+}
+
+fn is_active(conds: &[Cond]) -> bool {
+    conds.last().is_none_or(|c| c.active)
+}
+
+// the operators of `#if` which test for headers, they count as defined macros
+const HAS_INCLUDE: [&str; 2] = ["__has_include", "__has_include_next"];
+
+// the operators of `#if` which test what the compiler supports
+const HAS_FEATURE: [&str; 4] = [
+    "__has_attribute",
+    "__has_builtin",
+    "__has_feature",
+    "__has_extension",
+];
+
+// the builtin functions and types of crust, the ones used by the bundled stdarg.h
+const BUILTINS: [&str; 5] = [
+    "__builtin_va_arg",
+    "__builtin_va_copy",
+    "__builtin_va_end",
+    "__builtin_va_list",
+    "__builtin_va_start",
+];
+
+// the attributes of crust, `__attribute__` is not parsed yet
+const ATTRIBUTES: [&str; 0] = [];
+
+// the language features of clang's `__has_feature` which the parser supports, not
+// `c_atomic` because `__STDC_NO_ATOMICS__` is defined
+const FEATURES: [&str; 5] = [
+    "c_alignas",
+    "c_alignof",
+    "c_generic_selections",
+    "c_static_assert",
+    "c_thread_local",
+];
+
+/// Answer `__has_attribute`, `__has_builtin`, `__has_feature` and `__has_extension`
+/// for the operand `toks`.
+fn has_feature(operator: &str, toks: &[PPToken]) -> Result<bool, String> {
+    let name = match toks {
+        [tok] if tok.kind == PPTokenKind::Identifier => tok.text.as_str(),
+        _ => return Err(format!("macro \"{}\" requires an identifier", operator)),
+    };
+    Ok(match operator {
+        "__has_attribute" => {
+            // `__noreturn__` is the same attribute as `noreturn`
+            let name = name
+                .strip_prefix("__")
+                .and_then(|name| name.strip_suffix("__"))
+                .unwrap_or(name);
+            ATTRIBUTES.contains(&name)
+        }
+        "__has_builtin" => BUILTINS.contains(&name),
+        _ => FEATURES.contains(&name),
+    })
+}
+
+// answers the operator `__has_include` or `__has_include_next` for the operand,
+// the errors point at the operator
+type HasInclude<'a> = &'a dyn Fn(&PPToken, &[PPToken]) -> Result<bool, String>;
+
+/// Whether `tok` is an operator of `#if`: `defined` or one of the `__has_*` ones.
+fn is_condition_operator(tok: &PPToken) -> bool {
+    let name = tok.text.as_str();
+    tok.kind == PPTokenKind::Identifier
+        && (name == "defined" || HAS_INCLUDE.contains(&name) || HAS_FEATURE.contains(&name))
+}
+
+/// Take the operand `X` or `( X )` of `defined` from `input`, which holds the tokens after
+/// the operator in reverse order, C11 6.10.1p1.
+fn defined_operand(input: &mut Vec<PPToken>) -> Result<String, String> {
+    let paren = input.last().is_some_and(|tok| tok.is_punct("("));
+    if paren {
+        input.pop();
+    }
+    let name = match input.pop() {
+        Some(tok) if tok.kind == PPTokenKind::Identifier => tok.text,
+        _ => return Err("operator \"defined\" requires an identifier".to_string()),
+    };
+    if paren && !input.pop().is_some_and(|tok| tok.is_punct(")")) {
+        return Err("missing ')' after \"defined\"".to_string());
+    }
+    Ok(name)
+}
+
+/// Take the parenthesized operand of the `__has_*` `operator` from `input`, which holds
+/// the tokens after the operator in reverse order.
+fn has_operand(input: &mut Vec<PPToken>, operator: &str) -> Result<Vec<PPToken>, String> {
+    if !input.last().is_some_and(|tok| tok.is_punct("(")) {
+        return Err(format!("missing '(' before \"{}\" operand", operator));
+    }
+    input.pop();
+    let mut res = Vec::new();
+    let mut depth = 0;
+    while let Some(tok) = input.pop() {
+        if tok.is_punct(")") {
+            if depth == 0 {
+                return Ok(res);
+            }
+            depth -= 1;
+        } else if tok.is_punct("(") {
+            depth += 1;
+        }
+        res.push(tok);
+    }
+    Err(format!("missing ')' after \"{}\" operand", operator))
+}
+
+/// The `1` or `0` which replaces the operator `op` of `#if`.
+fn truth_value(value: bool, op: &PPToken) -> PPToken {
+    let mut tok = PPToken::new(PPTokenKind::Number, if value { "1" } else { "0" }, op.space);
+    tok.locate_at(op);
+    tok
+}
+
+/// Name of the macro tested by `#ifdef`/`#ifndef`, the diagnostics point at the directive
+/// name `at` in the file `file`.
+fn ifdef_name<'a>(toks: &'a [PPToken], at: &PPToken, file: &str) -> Result<&'a str, String> {
+    let directive = &at.text;
+    match toks.first() {
+        Some(tok) if tok.kind == PPTokenKind::Identifier => {
+            if toks.len() > 1 {
+                warning(&error_at(
+                    file,
+                    at,
+                    &format!("extra tokens at end of #{} directive", directive),
+                ));
+            }
+            Ok(&tok.text)
+        }
+        Some(_) => Err(error_at(file, at, "macro names must be identifiers")),
+        None => Err(error_at(
+            file,
+            at,
+            &format!("no macro name given in #{} directive", directive),
+        )),
+    }
+}
+
+// gcc's default limit of nested `#include`s
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// What the preprocessor remembers across the files of a translation unit.
+struct State {
+    // where the included files are read from
+    files: Box<dyn FileProvider>,
+    // the `#include` directives being processed as (file name, line), outermost first
+    stack: Vec<(String, usize)>,
+    // files which have `#pragma once`
+    once: HashSet<PathBuf>,
+    // the guard macros of the files wrapped in `#ifndef X` ... `#endif`
+    guards: HashMap<PathBuf, String>,
+    // the warning options changed by `#pragma GCC diagnostic`, like `cpp` for `-Wcpp`
+    diagnostics: HashMap<String, Severity>,
+    // the options saved by `#pragma GCC diagnostic push`
+    diagnostic_stack: Vec<HashMap<String, Severity>>,
+    // the files read so far, for -M
+    deps: Dependencies,
+    // the current file is a system header or is included by one
+    in_system_header: bool,
+    // the search directory of the current file, where `#include_next` continues
+    dir: Option<usize>,
+}
+
+impl State {
+    fn new(files: Box<dyn FileProvider>) -> State {
+        State {
+            files,
+            stack: Vec::new(),
+            once: HashSet::new(),
+            guards: HashMap::new(),
+            diagnostics: HashMap::new(),
+            diagnostic_stack: Vec::new(),
+            deps: Dependencies::default(),
+            in_system_header: false,
+            dir: None,
+        }
+    }
+
+    /// The same file has the same key however it is named.
+    fn file_key(&self, path: &Path) -> PathBuf {
+        self.files.canonicalize(path)
+    }
+
+    /// Print the warning `msg` of the option `-W<option>`, unless `#pragma GCC diagnostic`
+    /// ignores the option or turns it into an error.
+    fn warning(&self, option: &str, msg: &str) -> Result<(), String> {
+        match self.diagnostics.get(option) {
+            Some(Severity::Ignored) => Ok(()),
+            Some(Severity::Error) => Err(format!("{} [-Werror={}]", msg, option)),
+            Some(Severity::Warning) | None => {
+                warning(&format!("{} [-W{}]", msg, option));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// What `#pragma GCC diagnostic` makes of a warning option.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Severity {
+    Warning,
+    Error,
+    Ignored,
+}
+
+/// The macro tested by `#ifndef X` or `#if !defined X` on the first line of a file,
+/// the possible include guard of the file.
+fn guard_macro(line: &[PPToken]) -> Option<String> {
+    let name = match line.get(1).map(|tok| tok.text.as_str()) {
+        Some("ifndef") if line.len() == 3 => &line[2],
+        Some("if") if line.len() >= 5 && line[2].is_punct("!") && line[3].text == "defined" => {
+            match &line[4..] {
+                [name] => name,
+                [lparen, name, rparen] if lparen.is_punct("(") && rparen.is_punct(")") => name,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(name.text.clone()).filter(|_| name.kind == PPTokenKind::Identifier)
+}
+
+/// Report the include stack for an error in an `#include` directive, like gcc.
+fn print_include_stack(stack: &[(String, usize)]) {
+    for (idx, (file, line)) in stack.iter().rev().skip(1).enumerate() {
+        let prefix = if idx == 0 {
+            "In file included from"
+        } else {
+            "                 from"
+        };
+        let end = if idx + 2 == stack.len() { ':' } else { ',' };
+        eprintln!("{} {}:{}{}", prefix, file, line, end);
+    }
+}
+
+/// Undo `quote`, for the file name of `#line`.
+fn unquote(lit: &str) -> String {
+    let mut res = String::new();
+    let mut it = lit[1..lit.len() - 1].chars();
+    while let Some(c) = it.next() {
+        match c {
+            '\\' => res.extend(it.next()),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// A `#pragma` directive or a `_Pragma` operator, as given to the pragma handlers.
+struct Pragma<'a> {
+    // the tokens after the pragma name
+    args: &'a [PPToken],
+    // the token which the diagnostics point at
+    at: &'a PPToken,
+    file: &'a Path,
+    // the name of `file` in the diagnostics
+    name: &'a str,
+}
+
+impl Pragma<'_> {
+    /// Prefix `msg` with the location of the pragma.
+    fn locate(&self, msg: &str) -> String {
+        error_at(self.name, self.at, msg)
+    }
+
+    /// Print the warning `msg` at the location of the pragma.
+    fn warning(&self, msg: &str) {
+        warning(&self.locate(msg));
+    }
+}
+
+// a pragma handler returns true if the pragma is kept in the output
+type PragmaHandler = fn(&Pragma, &mut State) -> Result<bool, String>;
+
+// the pragmas known to crust, the others are kept in the output and ignored
+const PRAGMAS: [(&str, PragmaHandler); 4] = [
+    ("once", pragma_once),
+    ("pack", pragma_pack),
+    ("GCC diagnostic", pragma_gcc_diagnostic),
+    ("STDC", pragma_stdc),
+];
+
+/// `#pragma once`
+fn pragma_once(pragma: &Pragma, state: &mut State) -> Result<bool, String> {
+    if !pragma.args.is_empty() {
+        pragma.warning("extra tokens at end of #pragma once");
+    }
+    if state.stack.is_empty() {
+        pragma.warning("#pragma once in main file");
+    }
+    let key = state.file_key(pragma.file);
+    state.once.insert(key);
+    Ok(false)
+}
+
+/// `#pragma pack(n)`, `#pragma pack(push, n)`, `#pragma pack(pop)` and `#pragma pack()`
+///
+/// crust does not lay out structures with another alignment yet, so the pragma is only
+/// checked and kept in the output.
+fn pragma_pack(pragma: &Pragma, _: &mut State) -> Result<bool, String> {
+    let args = pragma.args;
+    let malformed = || {
+        pragma.warning("malformed '#pragma pack' - ignored");
+        Ok(false)
+    };
+    if args.len() < 2 || !args[0].is_punct("(") || !args[args.len() - 1].is_punct(")") {
+        return malformed();
+    }
+    let items: Vec<&str> = args[1..args.len() - 1]
+        .iter()
+        .map(|tok| tok.text.as_str())
+        .collect();
+    let items: Vec<&[&str]> = items.split(|item| *item == ",").collect();
+    if items.iter().skip(1).any(|item| item.len() != 1) || items[0].len() > 1 {
+        return malformed();
+    }
+    let check_alignment = |s: &str| match s.parse::<u64>() {
+        Ok(n) if n.is_power_of_two() && n <= 16 => {}
+        _ => pragma.warning(&format!(
+            "alignment must be a small power of two, not {}",
+            s
+        )),
+    };
+    match items[0].first() {
+        None | Some(&"pop") | Some(&"show") => {}
+        Some(&"push") => {
+            // an optional identifier, then an optional alignment
+            if let Some(n) = items[1..]
+                .iter()
+                .map(|item| item[0])
+                .find(|s| s.starts_with(|c: char| c.is_ascii_digit()))
+            {
+                check_alignment(n);
+            }
+        }
+        Some(n) if items.len() == 1 => check_alignment(n),
+        _ => return malformed(),
+    }
+    Ok(true)
+}
+
+/// `#pragma GCC diagnostic push`, `pop` and `warning|error|ignored "-Wname"`
+///
+/// They apply to the warnings of the preprocessor which have an option, like `-Wcpp`
+/// for `#warning`.
+fn pragma_gcc_diagnostic(pragma: &Pragma, state: &mut State) -> Result<bool, String> {
+    let args = pragma.args;
+    let severity = match args.first().map(|tok| tok.text.as_str()) {
+        Some("push") => {
+            state.diagnostic_stack.push(state.diagnostics.clone());
+            return Ok(true);
+        }
+        // without a push, pop goes back to the options of the command line
+        Some("pop") => {
+            state.diagnostics = state.diagnostic_stack.pop().unwrap_or_default();
+            return Ok(true);
+        }
+        Some("warning") => Severity::Warning,
+        Some("error") => Severity::Error,
+        Some("ignored") => Severity::Ignored,
+        _ => {
+            state.warning(
+                "pragmas",
+                &pragma.locate(
+                    "expected [error|warning|ignored|push|pop] after '#pragma GCC diagnostic'",
+                ),
+            )?;
+            return Ok(true);
+        }
+    };
+    match args.get(1) {
+        Some(tok) if tok.kind == PPTokenKind::StringLiteral && tok.text.starts_with("\"-W") => {
+            let option = &tok.text[3..tok.text.len() - 1];
+            state.diagnostics.insert(option.to_string(), severity);
+        }
+        _ => state.warning(
+            "pragmas",
+            &pragma.locate("missing option after '#pragma GCC diagnostic' kind"),
+        )?,
+    }
+    Ok(true)
+}
+
+/// The standard pragmas `#pragma STDC FP_CONTRACT|FENV_ACCESS|CX_LIMITED_RANGE ON|OFF|DEFAULT`,
+/// C11 6.10.6p2.
+fn pragma_stdc(pragma: &Pragma, _: &mut State) -> Result<bool, String> {
+    let texts: Vec<&str> = pragma.args.iter().map(|tok| tok.text.as_str()).collect();
+    match texts.as_slice() {
+        ["FP_CONTRACT", switch] | ["FENV_ACCESS", switch] | ["CX_LIMITED_RANGE", switch] => {
+            if !["ON", "OFF", "DEFAULT"].contains(switch) {
+                pragma.warning("expected 'ON', 'OFF' or 'DEFAULT' in STDC pragma");
+            }
+        }
+        _ => pragma.warning("unknown STDC pragma"),
+    }
+    Ok(true)
+}
+
+/// Handle `#pragma`, `toks` are the tokens after the `pragma` and the diagnostics point at
+/// `at` in the file `file` named `name`.
+/// Return true if the pragma is kept in the output.
+fn pragma(
+    toks: &[PPToken],
+    at: &PPToken,
+    file: &Path,
+    name: &str,
+    state: &mut State,
+) -> Result<bool, String> {
+    for (pragma_name, handler) in PRAGMAS.iter() {
+        let words = pragma_name.split(' ').count();
+        let matched = toks.len() >= words
+            && toks[..words]
+                .iter()
+                .map(|tok| tok.text.as_str())
+                .eq(pragma_name.split(' '));
+        if matched {
+            let pragma = Pragma {
+                args: &toks[words..],
+                at,
+                file,
+                name,
+            };
+            return handler(&pragma, state);
+        }
+    }
+    Ok(true)
+}
+
+/// Undo the string literal operand of `_Pragma`, C11 6.10.9p1.
+fn destringize(lit: &str) -> String {
+    let lit = lit.strip_prefix('L').unwrap_or(lit);
+    let mut res = String::new();
+    let mut it = lit[1..lit.len() - 1].chars().peekable();
+    while let Some(c) = it.next() {
+        match (c, it.peek()) {
+            ('\\', Some('\\')) | ('\\', Some('"')) => res.extend(it.next()),
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// Execute the `_Pragma ( string-literal )` operators in the macro expanded `toks` of the
+/// source file `file`, named `name`, C11 6.10.9. The pragmas kept in the output are put on
+/// lines of their own.
+fn pragma_operator(
+    toks: Vec<PPToken>,
+    file: &Path,
+    name: &str,
+    state: &mut State,
+) -> Result<Vec<PPToken>, String> {
+    let mut res = Vec::new();
+    let mut idx = 0;
+    let mut after_pragma = false;
+    while idx < toks.len() {
+        let tok = &toks[idx];
+        if tok.kind != PPTokenKind::Identifier || tok.text != "_Pragma" {
+            let mut tok = tok.clone();
+            tok.bol |= after_pragma;
+            after_pragma = false;
+            res.push(tok);
+            idx += 1;
+            continue;
+        }
+        let lit = match &toks[idx + 1..] {
+            [lparen, lit, rparen, ..]
+                if lparen.is_punct("(")
+                    && lit.kind == PPTokenKind::StringLiteral
+                    && rparen.is_punct(")") =>
+            {
+                lit
+            }
+            _ => {
+                return Err(error_at(
+                    name,
+                    tok,
+                    "_Pragma takes a parenthesized string literal",
+                ))
+            }
+        };
+        let mut line = tokenize(&format!("#pragma {}", destringize(&lit.text)));
+        for t in &mut line {
+            t.locate_at(tok);
+        }
+        if pragma(&line[2..], tok, file, name, state)? {
+            res.push(directive_token(&line));
+        }
+        after_pragma = true;
+        idx += 4;
+    }
+    Ok(res)
+}
+
+/// A directive which is kept in the output as a single token.
+fn directive_token(line: &[PPToken]) -> PPToken {
+    let mut tok = PPToken::new(
+        PPTokenKind::Directive,
+        detokenize(line, false).trim_end(),
+        false,
+    );
+    tok.bol = true;
+    tok.locate_at(&line[0]);
+    tok
+}
+
+/// The C standard selected with `-std=`.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Standard {
+    /// value of `__STDC_VERSION__`, None for C89
+    pub version: Option<&'static str>,
+    /// the GNU dialect, which also defines macros outside of the reserved namespace
+    pub gnu: bool,
+}
+
+impl Default for Standard {
+    fn default() -> Standard {
+        Standard {
+            version: Some("201112L"),
+            gnu: false,
+        }
+    }
+}
+
+impl FromStr for Standard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Standard, String> {
+        let (gnu, name) = match s.strip_prefix("gnu") {
+            Some(name) => (true, name),
+            None => (false, s.strip_prefix('c').unwrap_or("")),
+        };
+        let version = match (name, s) {
+            ("89", _) | ("90", _) | (_, "iso9899:1990") => None,
+            ("99", _) | ("9x", _) | (_, "iso9899:1999") => Some("199901L"),
+            ("11", _) | ("1x", _) | (_, "iso9899:2011") => Some("201112L"),
+            ("17", _) | ("18", _) | (_, "iso9899:2017") | (_, "iso9899:2018") => Some("201710L"),
+            ("2x", _) => Some("202000L"),
+            _ => return Err(format!("unrecognized C standard '{}'", s)),
+        };
+        Ok(Standard { version, gnu })
+    }
+}
+
+/// A macro defined with `-D` or removed with `-U` on the command line.
+#[derive(PartialEq, Clone, Debug)]
+pub enum MacroOption {
+    /// the text after `#define`
+    Define(String),
+    Undef(String),
+}
+
+impl MacroOption {
+    /// `-D NAME` defines NAME as 1, `-D NAME=value` as value.
+    pub fn define(s: &str) -> MacroOption {
+        match s.find('=') {
+            Some(idx) => MacroOption::Define(format!("{} {}", &s[..idx], &s[idx + 1..])),
+            None => MacroOption::Define(format!("{} 1", s)),
+        }
+    }
+
+    pub fn undef(s: &str) -> MacroOption {
+        MacroOption::Undef(s.to_string())
+    }
+}
+
+/// Which `#define` and `#undef` directives are kept in the output of `-E`.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum MacroDump {
+    #[default]
+    None,
+    /// `-dD`, the whole directives
+    Definitions,
+    /// `-dN`, only the macro names
+    Names,
+}
+
+/// The options of the preprocessor from the command line.
+#[derive(Default, Clone, Debug)]
+pub struct Options {
+    pub include_paths: IncludePaths,
+    pub std: Standard,
+    /// `-D` and `-U` in command line order
+    pub macros: Vec<MacroOption>,
+    /// the files of `-include`
+    pub includes: Vec<PathBuf>,
+    pub dump: MacroDump,
+    /// print each macro expansion to stderr
+    pub trace_macros: bool,
+    /// replace the trigraphs, `-trigraphs`
+    pub trigraphs: bool,
+}
+
+// predefined macros for the x86_64 linux target, C11 6.10.8 and the common GCC ones
+const TARGET_MACROS: [(&str, &str); 32] = [
+    ("__crust__", "1"),
+    ("__STDC__", "1"),
+    ("__STDC_HOSTED__", "1"),
+    ("__STDC_UTF_16__", "1"),
+    ("__STDC_UTF_32__", "1"),
+    ("__x86_64__", "1"),
+    ("__x86_64", "1"),
+    ("__amd64__", "1"),
+    ("__amd64", "1"),
+    ("__linux__", "1"),
+    ("__linux", "1"),
+    ("__gnu_linux__", "1"),
+    ("__unix__", "1"),
+    ("__unix", "1"),
+    ("__ELF__", "1"),
+    ("__LP64__", "1"),
+    ("_LP64", "1"),
+    ("__CHAR_BIT__", "8"),
+    ("__SIZEOF_SHORT__", "2"),
+    ("__SIZEOF_INT__", "4"),
+    ("__SIZEOF_LONG__", "8"),
+    ("__SIZEOF_LONG_LONG__", "8"),
+    ("__SIZEOF_POINTER__", "8"),
+    ("__SIZEOF_SIZE_T__", "8"),
+    ("__SIZEOF_PTRDIFF_T__", "8"),
+    ("__SIZEOF_FLOAT__", "4"),
+    ("__SIZEOF_DOUBLE__", "8"),
+    ("__SIZEOF_LONG_DOUBLE__", "16"),
+    ("__ORDER_LITTLE_ENDIAN__", "1234"),
+    ("__ORDER_BIG_ENDIAN__", "4321"),
+    ("__ORDER_PDP_ENDIAN__", "3412"),
+    ("__BYTE_ORDER__", "__ORDER_LITTLE_ENDIAN__"),
+];
+
+// C11 features crust does not implement, C11 6.10.8.3
+const UNSUPPORTED_FEATURE_MACROS: [&str; 4] = [
+    "__STDC_NO_ATOMICS__",
+    "__STDC_NO_COMPLEX__",
+    "__STDC_NO_THREADS__",
+    "__STDC_NO_VLA__",
+];
+
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// `__DATE__` and `__TIME__` of the seconds since the epoch, in UTC.
+fn date_and_time(secs: u64) -> (String, String) {
+    // days to civil date, from http://howardhinnant.github.io/date_algorithms.html
+    let z = secs / 86400 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let date = format!("\"{} {:2} {}\"", MONTH_NAMES[month as usize - 1], day, year);
+    let secs = secs % 86400;
+    let time = format!(
+        "\"{:02}:{:02}:{:02}\"",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    );
+    (date, time)
+}
+
+/// The predefined macros, C11 6.10.8.
+fn predefined_macros(std: Standard) -> Result<HashMap<String, Macro>, String> {
+    let mut macros: Vec<(&str, String)> = TARGET_MACROS
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect();
+    if let Some(version) = std.version {
+        macros.push(("__STDC_VERSION__", version.to_string()));
+        if version >= "201112L" {
+            macros.extend(
+                UNSUPPORTED_FEATURE_MACROS
+                    .iter()
+                    .map(|name| (*name, "1".to_string())),
+            );
+        }
+    }
+    if std.gnu {
+        macros.push(("linux", "1".to_string()));
+        macros.push(("unix", "1".to_string()));
+    } else {
+        macros.push(("__STRICT_ANSI__", "1".to_string()));
+    }
+
+    // SOURCE_DATE_EPOCH fixes the date and time for reproducible builds
+    let secs = match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.parse::<u64>().map_err(|_| {
+            "environment variable SOURCE_DATE_EPOCH must expand to a non-negative integer"
+                .to_string()
+        })?,
+        Err(_) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    };
+    let (date, time) = date_and_time(secs);
+    macros.push(("__DATE__", date));
+    macros.push(("__TIME__", time));
+
+    let type_macros = headers::type_macros();
+    macros.extend(
+        type_macros
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.clone())),
+    );
 
-    Ok(after_cpp_str)
+    let mut res = HashMap::new();
+    for (name, value) in macros {
+        let (name, m) = parse_define(&tokenize(&format!("{} {}", name, value)), "<built-in>")?;
+        res.insert(name, m);
+    }
+    let dynamic_macros = [
+        ("__FILE__", DynamicMacro::File),
+        ("__LINE__", DynamicMacro::Line),
+        ("__COUNTER__", DynamicMacro::Counter),
+    ];
+    for (name, dynamic) in dynamic_macros.iter() {
+        res.insert(
+            name.to_string(),
+            Macro {
+                params: None,
+                variadic: false,
+                body: Vec::new(),
+                dynamic: Some(*dynamic),
+                file: "<built-in>".to_string(),
+                line: 0,
+            },
+        );
+    }
+    Ok(res)
+}
+
+/// The preprocessor of a translation unit, it owns the macros, the state of the
+/// included files and the options.
+///
+/// The included files are read from the disk, unless another `FileProvider` is given
+/// to `with_files`.
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use crust::cpp::{self, MemoryFileSystem};
+/// use std::path::Path;
+///
+/// let mut files = MemoryFileSystem::new();
+/// files.insert("config.h", "#define SIZE 4\n");
+/// let mut preprocessor =
+///     cpp::Preprocessor::with_files(cpp::Options::default(), Box::new(files))?;
+/// let source = "#include \"config.h\"\nint buf[SIZE];\n".to_string();
+/// let tokens = preprocessor.preprocess(source, Path::new("main.c"))?;
+/// assert_eq!(cpp::detokenize(&tokens, false).trim(), "int buf[4];");
+/// # Ok(())
+/// # }
+/// ```
+pub struct Preprocessor {
+    options: Options,
+    macros: HashMap<String, Macro>,
+    // value of the next `__COUNTER__`
+    counter: Cell<usize>,
+    state: State,
+}
+
+impl Preprocessor {
+    /// A preprocessor with the predefined macros and the `-D` and `-U` of `options`.
+    pub fn new(options: Options) -> Result<Preprocessor, String> {
+        Preprocessor::with_files(options, Box::new(RealFileSystem))
+    }
+
+    /// Like `new`, but the included files are read from `files`.
+    pub fn with_files(
+        options: Options,
+        files: Box<dyn FileProvider>,
+    ) -> Result<Preprocessor, String> {
+        let mut preprocessor = Preprocessor {
+            macros: predefined_macros(options.std)?,
+            options,
+            counter: Cell::new(0),
+            state: State::new(Box::new(headers::WithBundledHeaders::new(files))),
+        };
+        // like gcc, all the `-D` and `-U` are processed in order before the `-include` files
+        for option in preprocessor.options.macros.clone() {
+            match option {
+                MacroOption::Define(def) => {
+                    preprocessor.define_macro(&tokenize(&def), "<command-line>")
+                }
+                MacroOption::Undef(name) => {
+                    let line = tokenize(&format!("#undef {}", name));
+                    preprocessor.undef_macro(&line[2..], &line[1], "<command-line>")
+                }
+            }
+            .map_err(|err| format!("<command-line>: {}", err))?;
+        }
+        Ok(preprocessor)
+    }
+
+    /// Preprocess the main source file of the translation unit, the result is a list
+    /// of preprocessing tokens for the lexer.
+    pub fn preprocess(
+        &mut self,
+        input: String,
+        path: &Path,
+    ) -> Result<Vec<PPToken>, Box<dyn error::Error>> {
+        self.state.deps.add(path, false);
+        let mut res = Vec::new();
+        if !self.options.includes.is_empty() {
+            // `-include file` acts like `#include "file"` at the start of the main file, but
+            // the file is searched in the working directory first
+            let source: String = self
+                .options
+                .includes
+                .iter()
+                .map(|file| format!("#include \"{}\"\n", file.display()))
+                .collect();
+            let command_line = Path::new("<command-line>");
+            res.push(PPToken::linemarker(
+                &command_line.to_string_lossy(),
+                1,
+                FileChange::Rename,
+            ));
+            let toks = self.tokenize_source(source, command_line)?;
+            self.directive_handler(toks, command_line, &mut res)?;
+        }
+        res.push(PPToken::linemarker(
+            &path.to_string_lossy(),
+            1,
+            FileChange::Rename,
+        ));
+        let toks = self.tokenize_source(input, path)?;
+        self.directive_handler(toks, path, &mut res)?;
+        Ok(res)
+    }
+
+    /// The files read by `preprocess`, for the make rules of `-M`.
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.state.deps
+    }
+
+    /// The definitions of all the macros in effect, for `-dM`.
+    pub fn macro_definitions(&self) -> String {
+        let mut names: Vec<&String> = self
+            .macros
+            .iter()
+            .filter(|(_, m)| m.dynamic.is_none())
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| macro_definition(name, &self.macros[name]) + "\n")
+            .collect()
+    }
+
+    /// Handle `#define`, `toks` are the tokens after the `define`.
+    fn define_macro(&mut self, toks: &[PPToken], file: &str) -> Result<(), String> {
+        let (name, m) = parse_define(toks, file)?;
+        if let Some(old) = self.macros.get(&name) {
+            if !same_definition(old, &m) {
+                warning(&error_at(
+                    file,
+                    &toks[0],
+                    &format!("\"{}\" redefined", name),
+                ));
+            }
+        }
+        self.macros.insert(name, m);
+        Ok(())
+    }
+
+    /// Handle `#undef`, `toks` are the tokens after the `undef`, the warnings point at `at` in
+    /// the file `file`.
+    fn undef_macro(&mut self, toks: &[PPToken], at: &PPToken, file: &str) -> Result<(), String> {
+        let name = macro_name("undef", toks)?;
+        if toks.len() > 1 {
+            warning(&error_at(
+                file,
+                at,
+                "extra tokens at end of #undef directive",
+            ));
+        }
+        if self.macros.get(&name).is_some_and(|m| m.dynamic.is_some()) {
+            warning(&error_at(file, at, &format!("undefining \"{}\"", name)));
+        }
+        self.macros.remove(&name);
+        Ok(())
+    }
+
+    /// Translation phases 1 to 3 on the contents of the source file `file`.
+    fn tokenize_source(&self, input: String, file: &Path) -> Result<Vec<PPToken>, String> {
+        // first translate trigraph into chars
+        let src = if self.options.trigraphs {
+            trigraph_processor(&input)
+        } else {
+            located_chars(&input)
+        };
+        // concatenate lines
+        let src = line_concat(src, &input, file);
+        // remove comment
+        let src = remove_comment(src, &input, file)?;
+        if !self.options.trigraphs {
+            warn_trigraphs(&src, &input, file);
+        }
+        Ok(tokenize_located(&src, &input))
+    }
+
+    /// The `#define` or `#undef` in `line` as it is kept in the output by `-dD` and `-dN`.
+    fn dump_token(&self, line: &[PPToken], directive: &str) -> PPToken {
+        let name = &line[2].text;
+        let text = match (directive, self.options.dump) {
+            ("define", MacroDump::Definitions) => macro_definition(name, &self.macros[name]),
+            _ => format!("#{} {}", directive, name),
+        };
+        let mut tok = directive_token(line);
+        tok.text = text;
+        tok
+    }
+
+    /// Translation phase 4 on the tokens of the source file `file`: execute the directives
+    /// and expand the macros. The result is appended to `res`.
+    fn directive_handler(
+        &mut self,
+        mut toks: Vec<PPToken>,
+        file: &Path,
+        res: &mut Vec<PPToken>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        // headers included by this file are searched from its own directory
+        let current_dir = file.parent();
+        // the file name and line numbers can be changed by `#line`
+        let mut name = file.to_string_lossy().into_owned();
+        let mut line_delta: isize = 0;
+        // text lines are collected until the next directive, so that the arguments of
+        // a function-like macro invocation can span several lines
+        let mut text: Vec<PPToken> = Vec::new();
+        let mut conds: Vec<Cond> = Vec::new();
+        // the include guard, which must enclose the whole file without `#else` or `#elif`
+        let mut guard: Option<String> = None;
+        let mut guard_closed = false;
+
+        let mut idx = 0;
+        while idx < toks.len() {
+            if idx == 0 {
+                let end = toks.iter().skip(1).position(|tok| tok.bol);
+                guard = guard_macro(&toks[..end.map_or(toks.len(), |len| len + 1)]);
+            } else if guard_closed {
+                // something after the `#endif` of the guard
+                guard = None;
+            }
+            let end = toks[idx + 1..]
+                .iter()
+                .position(|tok| tok.bol)
+                .map_or(toks.len(), |len| idx + 1 + len);
+            if line_delta != 0 {
+                for tok in &mut toks[idx..end] {
+                    tok.line = (tok.line as isize + line_delta) as usize;
+                }
+            }
+            let line = &toks[idx..end];
+            idx = end;
+            if !line[0].is_punct("#") {
+                if is_active(&conds) {
+                    for tok in line {
+                        if tok.kind == PPTokenKind::Identifier && tok.text == "__VA_ARGS__" {
+                            warning(&error_at(
+                                &name,
+                                tok,
+                                "__VA_ARGS__ can only appear in the expansion of a C99 variadic macro",
+                            ));
+                        }
+                    }
+                    text.extend_from_slice(line);
+                }
+                continue;
+            }
+            let expanded = self.expand(&text, &name)?;
+            res.extend(pragma_operator(expanded, file, &name, &mut self.state)?);
+            text.clear();
+
+            let directive = match line.get(1) {
+                Some(tok) if tok.kind == PPTokenKind::Identifier => tok.text.as_str(),
+                _ => "",
+            };
+            let args = line.get(2..).unwrap_or(&[]);
+            // `# 33 "file.h"` is a linemarker from preprocessed source
+            let marker = line
+                .get(1)
+                .is_some_and(|tok| tok.kind == PPTokenKind::Number);
+            if conds.len() == 1 {
+                match directive {
+                    "elif" | "else" => guard = None,
+                    "endif" => guard_closed = true,
+                    _ => {}
+                }
+            }
+            match directive {
+                "if" | "ifdef" | "ifndef" | "elif" | "else" | "endif" => {
+                    let has_include = |op: &PPToken, toks: &[PPToken]| {
+                        let next = op.text == "__has_include_next";
+                        let (_, header) = self.find_header(toks, op, next, &name, current_dir)?;
+                        Ok(header.is_some())
+                    };
+                    self.conditional_directive(&mut conds, line, &name, &has_include)?
+                }
+                // other directives in skipped groups are ignored
+                _ if !is_active(&conds) => {}
+                _ if directive == "line" || marker => {
+                    let (next, file, change) = self.line_directive(line, marker, &name)?;
+                    line_delta += next as isize - (line[0].line as isize + 1);
+                    if let Some(file) = file {
+                        name = file;
+                    }
+                    res.push(PPToken::linemarker(&name, next, change));
+                }
+                "define" | "undef" => {
+                    if directive == "define" {
+                        self.define_macro(args, &name)
+                    } else {
+                        self.undef_macro(args, &line[1], &name)
+                    }
+                    .map_err(|err| error_at(&name, &line[1], &err))?;
+                    if self.options.dump != MacroDump::None {
+                        res.push(self.dump_token(line, directive));
+                    }
+                }
+                "include" | "include_next" => {
+                    let next = directive == "include_next";
+                    if next && self.state.stack.is_empty() {
+                        warning(&error_at(
+                            &name,
+                            &line[1],
+                            "#include_next in primary source file",
+                        ));
+                    }
+                    self.state.stack.push((name.clone(), line[0].line));
+                    if self.include_file(line, next, &name, current_dir, res)? {
+                        res.push(PPToken::linemarker(
+                            &name,
+                            line[0].line + 1,
+                            FileChange::Return,
+                        ));
+                    }
+                    self.state.stack.pop();
+                }
+                "error" => {
+                    let msg = format!("#error {}", detokenize(args, false).trim_end());
+                    return Err(error_at(&name, &line[1], &msg).into());
+                }
+                "warning" => {
+                    let msg = format!("#warning {}", detokenize(args, false).trim_end());
+                    self.state
+                        .warning("cpp", &error_at(&name, &line[1], &msg))?;
+                }
+                "pragma" => {
+                    if pragma(args, &line[1], file, &name, &mut self.state)? {
+                        res.push(directive_token(line));
+                    }
+                }
+                "ident" | "sccs" => match args {
+                    [tok] if tok.kind == PPTokenKind::StringLiteral => {
+                        res.push(directive_token(line))
+                    }
+                    _ => {
+                        let msg = format!("invalid #{} directive", directive);
+                        return Err(error_at(&name, &line[1], &msg).into());
+                    }
+                },
+                // the null directive
+                _ if line.len() == 1 => {}
+                _ => {
+                    let msg = format!("invalid preprocessing directive #{}", line[1].text);
+                    return Err(error_at(&name, &line[1], &msg).into());
+                }
+            }
+        }
+        if let Some(cond) = conds.last() {
+            let msg = format!("unterminated #{}", cond.tok.text);
+            return Err(error_at(&name, &cond.tok, &msg).into());
+        }
+        let expanded = self.expand(&text, &name)?;
+        res.extend(pragma_operator(expanded, file, &name, &mut self.state)?);
+        if let (Some(guard), true) = (guard, guard_closed) {
+            let key = self.state.file_key(file);
+            self.state.guards.insert(key, guard);
+        }
+        Ok(())
+    }
+
+    /// Whether `name` is a macro for `defined`, `#ifdef` and `#ifndef`.
+    fn is_defined(&self, name: &str) -> bool {
+        HAS_INCLUDE.contains(&name) || HAS_FEATURE.contains(&name) || self.macros.contains_key(name)
+    }
+
+    /// Evaluate the expression of `#if` and `#elif`, `toks` are the tokens after the directive
+    /// name `at`.
+    ///
+    /// `defined` is answered during the expansion like the `__has_*` operators, so that it also
+    /// works when a macro like `#define HAS_X defined(X)` produces it, as in gcc and clang.
+    fn eval_condition(
+        &self,
+        toks: &[PPToken],
+        at: &PPToken,
+        file: &str,
+        has_include: HasInclude,
+    ) -> Result<bool, String> {
+        let toks = self.expand_tokens(toks, file, Some(has_include))?;
+        expr::eval(&toks).map_err(|err| error_at(file, at, &err))
+    }
+
+    /// Answer the operator `op` of `#if`, its operand is taken from `input`, which holds
+    /// the tokens after the operator in reverse order.
+    fn condition_operator(
+        &self,
+        op: &PPToken,
+        input: &mut Vec<PPToken>,
+        file: &str,
+        has_include: HasInclude,
+    ) -> Result<bool, String> {
+        let operator = op.text.as_str();
+        if operator == "defined" {
+            return defined_operand(input)
+                .map(|name| self.is_defined(&name))
+                .map_err(|err| error_at(file, op, &err));
+        }
+        let operand = has_operand(input, operator).map_err(|err| error_at(file, op, &err))?;
+        if HAS_INCLUDE.contains(&operator) {
+            has_include(op, &operand)
+        } else {
+            has_feature(operator, &operand).map_err(|err| error_at(file, op, &err))
+        }
+    }
+
+    /// Handle `#if`, `#ifdef`, `#ifndef`, `#elif`, `#else` and `#endif`, `line` is the
+    /// whole directive. The errors point at the directive name.
+    fn conditional_directive(
+        &self,
+        conds: &mut Vec<Cond>,
+        line: &[PPToken],
+        file: &str,
+        has_include: HasInclude,
+    ) -> Result<(), String> {
+        let at = &line[1];
+        let directive = at.text.as_str();
+        let toks = &line[2..];
+        let error = |msg: &str| Err(error_at(file, at, msg));
+        match directive {
+            "if" | "ifdef" | "ifndef" => {
+                let parent_active = is_active(conds);
+                let defined = || ifdef_name(toks, at, file).map(|name| self.is_defined(name));
+                // the expression of a skipped block is not evaluated
+                let active = parent_active
+                    && match directive {
+                        "if" => self.eval_condition(toks, at, file, has_include)?,
+                        "ifdef" => defined()?,
+                        _ => !defined()?,
+                    };
+                conds.push(Cond {
+                    tok: at.clone(),
+                    parent_active,
+                    active,
+                    taken: active,
+                    seen_else: false,
+                });
+            }
+            "elif" => {
+                let cond = match conds.last_mut() {
+                    Some(cond) => cond,
+                    None => return error("#elif without #if"),
+                };
+                if cond.seen_else {
+                    return error("#elif after #else");
+                }
+                if cond.taken || !cond.parent_active {
+                    cond.active = false;
+                } else {
+                    cond.active = self.eval_condition(toks, at, file, has_include)?;
+                    cond.taken = cond.active;
+                }
+            }
+            "else" => {
+                let cond = match conds.last_mut() {
+                    Some(cond) => cond,
+                    None => return error("#else without #if"),
+                };
+                if cond.seen_else {
+                    return error("#else after #else");
+                }
+                if !toks.is_empty() {
+                    let msg = "extra tokens at end of #else directive";
+                    self.state
+                        .warning("endif-labels", &error_at(file, at, msg))?;
+                }
+                cond.seen_else = true;
+                cond.active = cond.parent_active && !cond.taken;
+                cond.taken = true;
+            }
+            _ => {
+                if conds.pop().is_none() {
+                    return error("#endif without #if");
+                }
+                if !toks.is_empty() {
+                    let msg = "extra tokens at end of #endif directive";
+                    self.state
+                        .warning("endif-labels", &error_at(file, at, msg))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Name of the header in `#include`, and whether it is a `<...>` one.
+    ///
+    /// Any other tokens are macro expanded first, C11 6.10.2p4. Then a string literal is
+    /// taken as `"..."` and tokens from `<` to `>` are combined into `<...>`.
+    /// The errors point at `at`, the directive name or the `__has_include` operator.
+    fn header_name(
+        &self,
+        toks: &[PPToken],
+        at: &PPToken,
+        file: &str,
+    ) -> Result<(String, bool), String> {
+        let expanded;
+        let toks = match toks.first() {
+            Some(tok) if tok.kind == PPTokenKind::HeaderName => toks,
+            _ => {
+                expanded = self.expand(toks, file)?;
+                &expanded
+            }
+        };
+        let (text, len) = match toks.first() {
+            Some(tok) if tok.kind == PPTokenKind::HeaderName => (tok.text.clone(), 1),
+            Some(tok) if tok.kind == PPTokenKind::StringLiteral && tok.text.starts_with('"') => {
+                (tok.text.clone(), 1)
+            }
+            Some(tok) if tok.is_punct("<") => match toks.iter().position(|t| t.is_punct(">")) {
+                Some(end) => {
+                    let mut text = "<".to_string();
+                    for t in &toks[1..end] {
+                        if t.space && text.len() > 1 {
+                            text.push(' ');
+                        }
+                        text.push_str(&t.text);
+                    }
+                    text.push('>');
+                    (text, end + 1)
+                }
+                None => return Err(error_at(file, at, "missing terminating > character")),
+            },
+            _ => {
+                return Err(error_at(
+                    file,
+                    at,
+                    "#include expects \"FILENAME\" or <FILENAME>",
+                ))
+            }
+        };
+        if toks.len() > len {
+            warning(&error_at(
+                file,
+                at,
+                "extra tokens at end of #include directive",
+            ));
+        }
+        let name = &text[1..text.len() - 1];
+        if name.is_empty() {
+            return Err(error_at(file, at, "empty filename in #include"));
+        }
+        Ok((name.to_string(), text.starts_with('<')))
+    }
+
+    /// Handle `#line` (C11 6.10.4) and the linemarkers of preprocessed source like
+    /// `# 1 "header.h" 1`, `line` is the whole directive.
+    ///
+    /// Return the line number of the next line, the new file name if any and the flag.
+    fn line_directive(
+        &self,
+        line: &[PPToken],
+        marker: bool,
+        file: &str,
+    ) -> Result<(usize, Option<String>, FileChange), String> {
+        let at = &line[1];
+        let toks = if marker {
+            line[1..].to_vec()
+        } else {
+            self.expand(&line[2..], file)?
+        };
+        let directive = if marker { "#" } else { "#line" };
+        let line = match toks.first() {
+            Some(tok)
+                if tok.kind == PPTokenKind::Number
+                    && tok.text.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                tok.text.parse::<usize>().unwrap_or(usize::MAX)
+            }
+            Some(tok) => {
+                return Err(error_at(
+                    file,
+                    at,
+                    &format!(
+                        "\"{}\" after {} is not a positive integer",
+                        tok.text, directive
+                    ),
+                ))
+            }
+            None => {
+                return Err(error_at(
+                    file,
+                    at,
+                    &format!("unexpected end of {} directive", directive),
+                ))
+            }
+        };
+        if line > 2_147_483_647 || !marker && line == 0 {
+            warning(&error_at(file, at, "line number out of range"));
+        }
+        let name = match toks.get(1) {
+            Some(tok) if tok.kind == PPTokenKind::StringLiteral => Some(unquote(&tok.text)),
+            Some(tok) => {
+                return Err(error_at(
+                    file,
+                    at,
+                    &format!("invalid filename \"{}\"", tok.text),
+                ))
+            }
+            None => None,
+        };
+        let mut change = FileChange::Rename;
+        for tok in toks.iter().skip(2) {
+            match tok.text.as_str() {
+                _ if !marker => {
+                    warning(&error_at(
+                        file,
+                        at,
+                        "extra tokens at end of #line directive",
+                    ));
+                    break;
+                }
+                "1" => change = FileChange::Enter,
+                "2" => change = FileChange::Return,
+                // system header and extern "C"
+                "3" | "4" => {}
+                _ => {
+                    return Err(error_at(
+                        file,
+                        at,
+                        &format!("invalid flag \"{}\" in line directive", tok.text),
+                    ))
+                }
+            }
+        }
+        Ok((line, name, change))
+    }
+
+    /// Handle `#include` and `#include_next` (`next`), `line` is the whole directive.
+    /// The tokens of the included file are appended to `res`.
+    ///
+    /// Return false if the file is skipped because of `#pragma once` or its include guard.
+    fn include_file(
+        &mut self,
+        line: &[PPToken],
+        next: bool,
+        file: &str,
+        current_dir: Option<&Path>,
+        res: &mut Vec<PPToken>,
+    ) -> Result<bool, Box<dyn error::Error>> {
+        let at = &line[1];
+        let (path, system, dir) = match self.find_header(&line[2..], at, next, file, current_dir)? {
+            (_, Some(header)) => (header.path, header.system, header.dir),
+            (name, None) => {
+                let msg = format!("{}: No such file or directory", name);
+                return Err(error_at(file, at, &msg).into());
+            }
+        };
+        let state = &mut self.state;
+        let system = system || state.in_system_header;
+        state.deps.add(&path, system);
+        let key = state.file_key(&path);
+        if state.once.contains(&key) {
+            return Ok(false);
+        }
+        // a guarded file is not read again while its guard macro is defined
+        if let Some(guard) = state.guards.get(&key) {
+            if self.macros.contains_key(guard) {
+                return Ok(false);
+            }
+        }
+        if state.stack.len() >= MAX_INCLUDE_DEPTH {
+            print_include_stack(&state.stack);
+            let msg = format!(
+                "#include nested depth {} exceeds maximum of {}",
+                state.stack.len(),
+                MAX_INCLUDE_DEPTH
+            );
+            return Err(error_at(file, at, &msg).into());
+        }
+
+        let contents = state.files.read(&path)?;
+        res.push(PPToken::linemarker(
+            &path.to_string_lossy(),
+            1,
+            FileChange::Enter,
+        ));
+        let outer = std::mem::replace(&mut state.in_system_header, system);
+        let outer_dir = std::mem::replace(&mut state.dir, dir);
+        let toks = self.tokenize_source(contents, &path)?;
+        self.directive_handler(toks, &path, res)?;
+        self.state.in_system_header = outer;
+        self.state.dir = outer_dir;
+        Ok(true)
+    }
+
+    /// Find the header named by `toks` like `#include` or `#include_next` would.
+    fn find_header(
+        &self,
+        toks: &[PPToken],
+        at: &PPToken,
+        next: bool,
+        file: &str,
+        current_dir: Option<&Path>,
+    ) -> Result<(String, Option<Header>), String> {
+        let (name, angled) = self.header_name(toks, at, file)?;
+        let after = if next { self.state.dir } else { None };
+        let header =
+            self.options
+                .include_paths
+                .find(&*self.state.files, &name, angled, current_dir, after);
+        Ok((name, header))
+    }
+
+    /// Replace the parameters in the replacement list with the arguments and
+    /// handle the `#` and `##` operators.
+    ///
+    /// For a variadic macro `args` has no element for the variable argument if it is absent.
+    /// The errors point at `name`, the macro name of the invocation.
+    fn substitute(
+        &self,
+        m: &Macro,
+        name: &PPToken,
+        args: &[Vec<PPToken>],
+        file: &str,
+        has_include: Option<HasInclude>,
+    ) -> Result<Vec<PPToken>, String> {
+        let params: &[String] = match &m.params {
+            Some(params) => params,
+            None => &[],
+        };
+        let absent = Vec::new();
+        let arg_of = |tok: &PPToken| -> Option<&Vec<PPToken>> {
+            if tok.kind != PPTokenKind::Identifier {
+                return None;
+            }
+            params
+                .iter()
+                .position(|p| p == &tok.text)
+                .map(|i| args.get(i).unwrap_or(&absent))
+        };
+        let is_va_param = |tok: &PPToken| {
+            m.variadic && tok.kind == PPTokenKind::Identifier && params.last() == Some(&tok.text)
+        };
+        let va_absent = m.variadic && args.len() < params.len();
+        // the operand of `#` or `##`, which is not macro-expanded
+        let operand = |body: &[PPToken], idx: usize| -> Result<(Vec<PPToken>, usize), String> {
+            let tok = &body[idx];
+            if m.params.is_some() && tok.is_punct("#") {
+                let mut s = stringize(arg_of(&body[idx + 1]).unwrap())
+                    .map_err(|err| error_at(file, name, &err))?;
+                s.space = tok.space;
+                return Ok((vec![s], idx + 2));
+            }
+            Ok(match arg_of(tok) {
+                Some(arg) if arg.is_empty() => (
+                    vec![PPToken::new(PPTokenKind::Placemarker, "", tok.space)],
+                    idx + 1,
+                ),
+                Some(arg) => {
+                    let mut arg = arg.clone();
+                    arg[0].space = tok.space;
+                    (arg, idx + 1)
+                }
+                None => (vec![tok.clone()], idx + 1),
+            })
+        };
+
+        let va_opt_body;
+        let mut body = &m.body;
+        if m.variadic && body.iter().any(|tok| tok.text == "__VA_OPT__") {
+            let va_empty = match args.get(params.len() - 1) {
+                Some(arg) => self.expand_tokens(arg, file, has_include)?.is_empty(),
+                None => true,
+            };
+            va_opt_body = replace_va_opt(body, va_empty);
+            body = &va_opt_body;
+        }
+        let mut res: Vec<PPToken> = Vec::new();
+        let mut idx = 0;
+        while idx < body.len() {
+            let tok = &body[idx];
+            if tok.is_punct("##")
+                && idx > 0
+                && body[idx - 1].is_punct(",")
+                && body.get(idx + 1).is_some_and(is_va_param)
+            {
+                // GNU extension: in `, ## __VA_ARGS__` the comma is deleted if the variable
+                // argument is absent, otherwise `##` does nothing
+                if va_absent {
+                    res.pop();
+                } else {
+                    let arg = arg_of(&body[idx + 1]).unwrap();
+                    let mut arg = self.expand_tokens(arg, file, has_include)?;
+                    if let Some(first) = arg.first_mut() {
+                        first.space = body[idx + 1].space;
+                    }
+                    res.extend(arg);
+                }
+                idx += 2;
+            } else if tok.is_punct("##") {
+                // the definition was checked that `##` is neither the first nor the last token,
+                // also not of a `__VA_OPT__`
+                let lhs = match res.pop() {
+                    Some(lhs) if idx + 1 < body.len() => lhs,
+                    _ => {
+                        let msg = "'##' cannot appear at either end of a macro expansion";
+                        return Err(error_at(file, name, msg));
+                    }
+                };
+                let (rhs, next) = operand(body, idx + 1)?;
+                res.push(paste(&lhs, &rhs[0]).map_err(|err| error_at(file, name, &err))?);
+                res.extend_from_slice(&rhs[1..]);
+                idx = next;
+            } else if idx + 1 < body.len() && body[idx + 1].is_punct("##")
+                || m.params.is_some() && tok.is_punct("#")
+            {
+                let (toks, next) = operand(body, idx)?;
+                res.extend(toks);
+                idx = next;
+            } else if let Some(arg) = arg_of(tok) {
+                // argument is completely macro replaced before substitution
+                let mut arg = self.expand_tokens(arg, file, has_include)?;
+                if let Some(first) = arg.first_mut() {
+                    first.space = tok.space;
+                }
+                res.extend(arg);
+                idx += 1;
+            } else {
+                res.push(tok.clone());
+                idx += 1;
+            }
+        }
+        res.retain(|tok| tok.kind != PPTokenKind::Placemarker);
+        Ok(res)
+    }
+
+    /// Expand all the macros in the tokens with Prosser's hide set algorithm.
+    ///
+    /// Every token carries the names of the macros it was produced by. A macro name found in
+    /// its own hide set is not replaced, also not in later rescans, so self-referential
+    /// macros like `#define foo foo` stop expanding (C11 6.10.3.4p2).
+    ///
+    /// `file` is the name of the source file the tokens come from, for `__FILE__`.
+    fn expand(&self, toks: &[PPToken], file: &str) -> Result<Vec<PPToken>, String> {
+        self.expand_tokens(toks, file, None)
+    }
+
+    /// Like `expand`, with `has_include` for the expression of `#if` and `#elif`.
+    ///
+    /// `defined` and the `__has_*` operators are answered when the rescan reaches them, so
+    /// they also work when a macro like `#define HAS(x) __has_builtin(x)` produces them.
+    /// Their operands are taken from the rest of the tokens before those are expanded.
+    fn expand_tokens(
+        &self,
+        toks: &[PPToken],
+        file: &str,
+        has_include: Option<HasInclude>,
+    ) -> Result<Vec<PPToken>, String> {
+        // tokens still to be scanned in reverse order, the replacement of a macro is pushed
+        // back and rescanned together with the rest of the tokens
+        let mut input: Vec<PPToken> = toks.iter().rev().cloned().collect();
+        let mut res = Vec::new();
+        while let Some(tok) = input.pop() {
+            if let Some(has_include) = has_include.filter(|_| is_condition_operator(&tok)) {
+                let value = self.condition_operator(&tok, &mut input, file, has_include)?;
+                res.push(truth_value(value, &tok));
+                continue;
+            }
+            let m = match tok.kind {
+                PPTokenKind::Identifier if !tok.hide_set.contains(&tok.text) => {
+                    self.macros.get(&tok.text)
+                }
+                _ => None,
+            };
+            let m = match m {
+                Some(m) => m,
+                None => {
+                    res.push(tok);
+                    continue;
+                }
+            };
+            let (replaced, mut hide_set) = match &m.params {
+                None => {
+                    let replaced = match m.dynamic {
+                        Some(dynamic) => vec![dynamic.replace(&tok, file, &self.counter)],
+                        None => self.substitute(m, &tok, &[], file, has_include)?,
+                    };
+                    (replaced, tok.hide_set.clone())
+                }
+                Some(params) => match collect_args(&mut input, m, &tok.text)
+                    .map_err(|err| error_at(file, &tok, &err))?
+                {
+                    None => {
+                        // a function-like macro name not followed by `(` is not an invocation
+                        res.push(tok);
+                        continue;
+                    }
+                    Some((mut args, rparen)) => {
+                        if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+                            args.clear();
+                        }
+                        if let Err(err) = check_arg_count(m, params, args.len(), &tok.text) {
+                            return Err(error_at(file, &tok, &err));
+                        }
+                        let hide_set = tok
+                            .hide_set
+                            .intersection(&rparen.hide_set)
+                            .cloned()
+                            .collect();
+                        (
+                            self.substitute(m, &tok, &args, file, has_include)?,
+                            hide_set,
+                        )
+                    }
+                },
+            };
+            if self.options.trace_macros {
+                eprintln!(
+                    "{}:{}: expanding macro '{}' to '{}'",
+                    file,
+                    tok.line,
+                    tok.text,
+                    detokenize(&replaced, false).trim_end()
+                );
+            }
+            hide_set.insert(tok.text.clone());
+            let expansion = Arc::new(Expansion {
+                name: tok.text.clone(),
+                file: m.file.clone(),
+                line: m.line,
+                parent: tok.expansion.clone(),
+            });
+            for (idx, mut t) in replaced.into_iter().enumerate().rev() {
+                t.hide_set.extend(hide_set.iter().cloned());
+                t.expansion = Some(expansion.clone());
+                t.locate_at(&tok);
+                if idx == 0 {
+                    t.space = tok.space;
+                    t.bol = tok.bol;
+                }
+                input.push(t);
+            }
+        }
+        Ok(res)
+    }
 }
@@ -0,0 +1,93 @@
+// -----------------------------------------------------------------------------
+// cpp/deps.rs: make rules for the files read by the preprocessor, -M and -MD
+// -----------------------------------------------------------------------------
+// The output follows gcc: the rule is wrapped before column 72 and with -MP
+// every header gets an empty rule, so make does not fail when it is removed.
+
+use std::path::{Path, PathBuf};
+
+use super::BUNDLED_INCLUDE_DIR;
+
+// lines of the rule are wrapped when they would be longer than this
+const MAX_COLUMN: usize = 72;
+
+/// The files read while preprocessing, the main file first.
+///
+/// The bundled headers are left out, like the system headers with -MM: they are built
+/// into crust and make could not find them.
+#[derive(Default, Clone, Debug)]
+pub struct Dependencies {
+    // (file, is a system header or included by one)
+    files: Vec<(PathBuf, bool)>,
+}
+
+impl Dependencies {
+    pub(super) fn add(&mut self, path: &Path, system: bool) {
+        if path.starts_with(BUNDLED_INCLUDE_DIR) {
+            return;
+        }
+        if !self.files.iter().any(|(file, _)| file == path) {
+            self.files.push((path.to_path_buf(), system));
+        }
+    }
+
+    /// The make rule with `targets` depending on the files, the system headers are
+    /// left out unless `system` is set. With `phony` each header gets an empty rule.
+    pub fn make_rule(&self, targets: &[String], system: bool, phony: bool) -> String {
+        let files: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(_, is_system)| system || !is_system)
+            .map(|(file, _)| escape(&file.to_string_lossy()))
+            .collect();
+
+        let mut res = String::new();
+        let mut column = 0;
+        let mut write = |res: &mut String, name: &str| {
+            if column > 0 {
+                if column + name.len() > MAX_COLUMN {
+                    res.push_str(" \\\n");
+                    column = 0;
+                }
+                res.push(' ');
+                column += 1;
+            }
+            res.push_str(name);
+            column += name.len();
+        };
+        for target in targets {
+            write(&mut res, target);
+        }
+        res.push(':');
+        for file in &files {
+            write(&mut res, file);
+        }
+        res.push('\n');
+        if phony {
+            for file in files.iter().skip(1) {
+                res.push_str(&format!("{}:\n", file));
+            }
+        }
+        res
+    }
+}
+
+/// Escape the characters which are special to make in a file name.
+fn escape(name: &str) -> String {
+    let mut res = String::new();
+    let mut backslashes = 0;
+    for c in name.chars() {
+        match c {
+            ' ' | '\t' => {
+                // the backslashes before a blank have to be escaped too
+                res.push_str(&"\\".repeat(backslashes + 1));
+            }
+            '$' => res.push('$'),
+            '#' => res.push('\\'),
+            _ => {}
+        }
+        backslashes = if c == '\\' { backslashes + 1 } else { 0 };
+        res.push(c);
+    }
+    res
+}
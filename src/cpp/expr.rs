@@ -0,0 +1,398 @@
+// -----------------------------------------------------------------------------
+// cpp/expr.rs: integer constant expression evaluator for `#if` and `#elif`
+// -----------------------------------------------------------------------------
+// C11 6.10.1p4: all signed integer types act as intmax_t and all unsigned
+// integer types act as uintmax_t, so a value is 64 bits plus its signedness.
+
+use super::{PPToken, PPTokenKind};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+struct Value {
+    bits: u64,
+    unsigned: bool,
+}
+
+impl Value {
+    fn signed(v: i64) -> Value {
+        Value {
+            bits: v as u64,
+            unsigned: false,
+        }
+    }
+
+    fn bool(b: bool) -> Value {
+        Value::signed(b as i64)
+    }
+
+    fn is_true(self) -> bool {
+        self.bits != 0
+    }
+
+    fn as_i64(self) -> i64 {
+        self.bits as i64
+    }
+}
+
+/// Parse an integer constant, the suffix only makes a difference to the signedness.
+fn parse_integer(s: &str) -> Result<Value, String> {
+    let lower = s.to_ascii_lowercase();
+    let (radix, digits) = if let Some(hex) = lower.strip_prefix("0x") {
+        (16, hex)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        (2, bin)
+    } else if lower.starts_with('0') {
+        (8, &lower[..])
+    } else {
+        (10, &lower[..])
+    };
+    let end = digits
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(end);
+    let explicit_unsigned = match suffix {
+        "" | "l" | "ll" => false,
+        "u" | "ul" | "lu" | "ull" | "llu" => true,
+        _ => {
+            if s.contains('.') || (radix != 16 && lower.contains('e')) || lower.contains('p') {
+                return Err("floating constant in preprocessor expression".to_string());
+            }
+            return Err(format!("invalid integer constant `{}` in #if", s));
+        }
+    };
+    if digits.is_empty() && radix != 8 {
+        return Err(format!("invalid integer constant `{}` in #if", s));
+    }
+    let bits = match u64::from_str_radix(if digits.is_empty() { "0" } else { digits }, radix) {
+        Ok(bits) => bits,
+        Err(_) => {
+            return Err(format!(
+                "integer constant `{}` is too large for its type",
+                s
+            ))
+        }
+    };
+    // a constant that does not fit in intmax_t can only be uintmax_t
+    Ok(Value {
+        bits,
+        unsigned: explicit_unsigned || bits > i64::MAX as u64,
+    })
+}
+
+/// Value of a character constant, plain char is signed like on x86-64.
+fn parse_char(s: &str) -> Result<Value, String> {
+    let quote = s.find('\'').unwrap();
+    let wide = quote != 0;
+    let body: Vec<char> = s[quote + 1..s.len() - 1].chars().collect();
+    let mut values: Vec<u32> = Vec::new();
+    let mut idx = 0;
+    while idx < body.len() {
+        let c = body[idx];
+        idx += 1;
+        if c != '\\' {
+            values.push(c as u32);
+            continue;
+        }
+        let e = match body.get(idx) {
+            Some(&e) => e,
+            None => return Err(format!("invalid character constant {}", s)),
+        };
+        idx += 1;
+        let v = match e {
+            'a' => 0x07,
+            'b' => 0x08,
+            'e' => 0x1B,
+            'f' => 0x0C,
+            'n' => 0x0A,
+            'r' => 0x0D,
+            't' => 0x09,
+            'v' => 0x0B,
+            '\\' | '\'' | '"' | '?' => e as u32,
+            'x' => {
+                let mut v: u32 = 0;
+                let start = idx;
+                while idx < body.len() && body[idx].is_ascii_hexdigit() {
+                    v = v.wrapping_mul(16) + body[idx].to_digit(16).unwrap();
+                    idx += 1;
+                }
+                if start == idx {
+                    return Err("\\x used with no following hex digits".to_string());
+                }
+                v
+            }
+            '0'..='7' => {
+                let mut v = e.to_digit(8).unwrap();
+                let mut n = 1;
+                while n < 3 && idx < body.len() && body[idx].is_digit(8) {
+                    v = v * 8 + body[idx].to_digit(8).unwrap();
+                    idx += 1;
+                    n += 1;
+                }
+                v
+            }
+            _ => return Err(format!("unknown escape sequence '\\{}'", e)),
+        };
+        values.push(v);
+    }
+    if values.is_empty() {
+        return Err("empty character constant".to_string());
+    }
+    if wide {
+        return Ok(Value::signed(i64::from(*values.last().unwrap())));
+    }
+    // multi-character constants are implementation-defined, do the same as gcc
+    let mut v: i64 = 0;
+    for c in values.iter() {
+        v = (v << 8) | i64::from(*c as u8);
+    }
+    if values.len() == 1 {
+        v = i64::from(v as u8 as i8);
+    } else {
+        v = i64::from(v as i32);
+    }
+    Ok(Value::signed(v))
+}
+
+// binary operators and their precedences, higher binds tighter
+fn precedence(op: &str) -> Option<u8> {
+    let prec = match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | ">" | "<=" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        _ => return None,
+    };
+    Some(prec)
+}
+
+fn shift(l: Value, r: Value, left: bool) -> Value {
+    let count = r.as_i64();
+    let (count, left) = if !r.unsigned && count < 0 {
+        (count.wrapping_neg() as u64, !left)
+    } else {
+        (r.bits, left)
+    };
+    let bits = if left {
+        if count >= 64 {
+            0
+        } else {
+            l.bits << count
+        }
+    } else if l.unsigned {
+        if count >= 64 {
+            0
+        } else {
+            l.bits >> count
+        }
+    } else {
+        (l.as_i64() >> count.min(63)) as u64
+    };
+    Value {
+        bits,
+        unsigned: l.unsigned,
+    }
+}
+
+fn binary(op: &str, l: Value, r: Value) -> Result<Value, String> {
+    // usual arithmetic conversions: if either operand is unsigned, both are
+    let unsigned = l.unsigned || r.unsigned;
+    let arith = |bits: u64| Value { bits, unsigned };
+    let res = match op {
+        "*" => arith(l.bits.wrapping_mul(r.bits)),
+        "/" | "%" => {
+            if r.bits == 0 {
+                return Err("division by zero in #if".to_string());
+            }
+            let bits = match (op, unsigned) {
+                ("/", true) => l.bits / r.bits,
+                ("%", true) => l.bits % r.bits,
+                ("/", false) => l.as_i64().wrapping_div(r.as_i64()) as u64,
+                _ => l.as_i64().wrapping_rem(r.as_i64()) as u64,
+            };
+            arith(bits)
+        }
+        "+" => arith(l.bits.wrapping_add(r.bits)),
+        "-" => arith(l.bits.wrapping_sub(r.bits)),
+        "<<" => shift(l, r, true),
+        ">>" => shift(l, r, false),
+        "<" | ">" | "<=" | ">=" => {
+            let ord = if unsigned {
+                l.bits.cmp(&r.bits)
+            } else {
+                l.as_i64().cmp(&r.as_i64())
+            };
+            Value::bool(match op {
+                "<" => ord.is_lt(),
+                ">" => ord.is_gt(),
+                "<=" => ord.is_le(),
+                _ => ord.is_ge(),
+            })
+        }
+        "==" => Value::bool(l.bits == r.bits),
+        "!=" => Value::bool(l.bits != r.bits),
+        "&" => arith(l.bits & r.bits),
+        "^" => arith(l.bits ^ r.bits),
+        "|" => arith(l.bits | r.bits),
+        _ => unreachable!("not a binary operator {}", op),
+    };
+    Ok(res)
+}
+
+struct ExprParser<'a> {
+    toks: &'a [PPToken],
+    pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn peek(&self) -> Option<&'a PPToken> {
+        self.toks.get(self.pos)
+    }
+
+    fn peek_punct(&self) -> Option<&'a str> {
+        match self.peek() {
+            Some(tok) if tok.kind == PPTokenKind::Punctuator => Some(&tok.text),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), String> {
+        match self.peek() {
+            Some(tok) if tok.is_punct(p) => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(tok) => Err(format!(
+                "expected '{}' in expression, found `{}`",
+                p, tok.text
+            )),
+            None => Err(format!("expected '{}' at end of expression", p)),
+        }
+    }
+
+    // expression: conditional_expression (',' conditional_expression)*
+    //
+    // `eval` is false inside the operand that is not evaluated because of
+    // `&&`, `||` or `?:`, errors like division by zero are not reported there.
+    fn expression(&mut self, eval: bool) -> Result<Value, String> {
+        let mut v = self.conditional(eval)?;
+        while self.peek_punct() == Some(",") {
+            self.pos += 1;
+            v = self.conditional(eval)?;
+        }
+        Ok(v)
+    }
+
+    fn conditional(&mut self, eval: bool) -> Result<Value, String> {
+        let cond = self.binary(1, eval)?;
+        if self.peek_punct() != Some("?") {
+            return Ok(cond);
+        }
+        self.pos += 1;
+        let l = self.expression(eval && cond.is_true())?;
+        self.expect(":")?;
+        let r = self.conditional(eval && !cond.is_true())?;
+        let mut v = if cond.is_true() { l } else { r };
+        v.unsigned = l.unsigned || r.unsigned;
+        Ok(v)
+    }
+
+    fn binary(&mut self, min_prec: u8, eval: bool) -> Result<Value, String> {
+        let mut l = self.unary(eval)?;
+        while let Some(op) = self.peek_punct() {
+            let prec = match precedence(op) {
+                Some(prec) if prec >= min_prec => prec,
+                _ => break,
+            };
+            self.pos += 1;
+            l = match op {
+                "&&" => {
+                    let r = self.binary(prec + 1, eval && l.is_true())?;
+                    Value::bool(l.is_true() && r.is_true())
+                }
+                "||" => {
+                    let r = self.binary(prec + 1, eval && !l.is_true())?;
+                    Value::bool(l.is_true() || r.is_true())
+                }
+                _ => {
+                    let r = self.binary(prec + 1, eval)?;
+                    match binary(op, l, r) {
+                        Ok(v) => v,
+                        Err(_) if !eval => Value::signed(0),
+                        Err(msg) => return Err(msg),
+                    }
+                }
+            };
+        }
+        Ok(l)
+    }
+
+    fn unary(&mut self, eval: bool) -> Result<Value, String> {
+        let op = match self.peek_punct() {
+            Some(op @ "+") | Some(op @ "-") | Some(op @ "~") | Some(op @ "!") => op,
+            _ => return self.primary(eval),
+        };
+        self.pos += 1;
+        let v = self.unary(eval)?;
+        let res = match op {
+            "+" => v,
+            "-" => Value {
+                bits: v.bits.wrapping_neg(),
+                unsigned: v.unsigned,
+            },
+            "~" => Value {
+                bits: !v.bits,
+                unsigned: v.unsigned,
+            },
+            _ => Value::bool(!v.is_true()),
+        };
+        Ok(res)
+    }
+
+    fn primary(&mut self, eval: bool) -> Result<Value, String> {
+        let tok = match self.peek() {
+            Some(tok) => tok,
+            None => return Err("#if expression ends unexpectedly".to_string()),
+        };
+        self.pos += 1;
+        match tok.kind {
+            PPTokenKind::Number => parse_integer(&tok.text),
+            PPTokenKind::CharConstant => parse_char(&tok.text),
+            // identifiers left after macro expansion are replaced with 0
+            PPTokenKind::Identifier => Ok(Value::signed(0)),
+            PPTokenKind::Punctuator if tok.text == "(" => {
+                let v = self.expression(eval)?;
+                match self.peek() {
+                    Some(tok) if tok.is_punct(")") => {
+                        self.pos += 1;
+                        Ok(v)
+                    }
+                    _ => Err("missing ')' in expression".to_string()),
+                }
+            }
+            PPTokenKind::StringLiteral => Err(format!("token {} is not valid in #if", tok.text)),
+            _ => Err(format!("token `{}` is not valid in #if", tok.text)),
+        }
+    }
+}
+
+/// Evaluate the controlling expression of `#if`/`#elif`, `defined` and macros
+/// should have been replaced by the caller.
+pub(super) fn eval(toks: &[PPToken]) -> Result<bool, String> {
+    if toks.is_empty() {
+        return Err("#if with no expression".to_string());
+    }
+    let mut parser = ExprParser { toks, pos: 0 };
+    let v = parser.expression(true)?;
+    if let Some(tok) = parser.peek() {
+        return Err(format!(
+            "missing binary operator before token `{}`",
+            tok.text
+        ));
+    }
+    Ok(v.is_true())
+}
@@ -0,0 +1,114 @@
+// -----------------------------------------------------------------------------
+// cpp/files.rs: where the preprocessor reads the included files from
+// -----------------------------------------------------------------------------
+// The preprocessor never touches the disk directly, so tests and editors can
+// hand it a whole project, or unsaved buffers, from memory.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+
+/// The files the preprocessor can `#include`.
+pub trait FileProvider: Send {
+    /// Whether `path` is a file that can be read.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// The contents of the file `path`.
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// The same file has the same key however it is named, for `#pragma once` and
+    /// the include guards.
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+}
+
+/// The files on the disk.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct RealFileSystem;
+
+impl FileProvider for RealFileSystem {
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+/// Files kept in memory, `dir/../a.h` and `./a.h` name the file `a.h`.
+///
+/// ```
+/// use crust::cpp::{self, MemoryFileSystem};
+/// use std::path::Path;
+///
+/// let mut files = MemoryFileSystem::new();
+/// files.insert("inc/a.h", "#include \"b.h\"\nint a = B;\n");
+/// files.insert("inc/b.h", "#define B 1\n");
+/// let mut preprocessor =
+///     cpp::Preprocessor::with_files(cpp::Options::default(), Box::new(files)).unwrap();
+/// let toks = preprocessor
+///     .preprocess("#include \"inc/a.h\"\n".to_string(), Path::new("main.c"))
+///     .unwrap();
+/// assert_eq!(cpp::detokenize(&toks, false).trim(), "int a = 1;");
+/// ```
+#[derive(Default, Clone, Debug)]
+pub struct MemoryFileSystem {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    /// Add the file `path`, or replace its contents.
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P, contents: &str) {
+        self.files
+            .insert(normalize(path.as_ref()), contents.to_string());
+    }
+}
+
+impl FileProvider for MemoryFileSystem {
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.contains_key(&normalize(path))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.files.get(&normalize(path)).cloned().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{}: No such file or directory", path.display()),
+            )
+        })
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        normalize(path)
+    }
+}
+
+/// Remove the `.` components and resolve the `..` ones without looking at the disk.
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match res.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    res.pop();
+                }
+                // `/..` is `/`
+                Some(Component::RootDir) => {}
+                _ => res.push(".."),
+            },
+            _ => res.push(component),
+        }
+    }
+    res
+}
@@ -0,0 +1,233 @@
+// -----------------------------------------------------------------------------
+// cpp/headers.rs: the freestanding headers bundled with crust, C11 4p6
+// -----------------------------------------------------------------------------
+// The headers are embedded in the binary and served from BUNDLED_INCLUDE_DIR,
+// which is searched before the system directories like gcc's own include
+// directory. They know the type model only through the predefined macros of
+// `type_macros`, which have the same names as gcc's.
+
+use super::files::{FileProvider, MemoryFileSystem};
+use crate::symtable::{
+    X86_64_CHAR_BYTES, X86_64_INT_BYTES, X86_64_LONG_BYTES, X86_64_LONG_LONG_BYTES,
+    X86_64_POINTER_BYTES, X86_64_SHORT_BYTES,
+};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The directory of the bundled headers, it is not on the disk.
+pub const BUNDLED_INCLUDE_DIR: &str = "<crust>/include";
+
+const HEADERS: [(&str, &str); 9] = [
+    ("float.h", include_str!("include/float.h")),
+    ("iso646.h", include_str!("include/iso646.h")),
+    ("limits.h", include_str!("include/limits.h")),
+    ("stdalign.h", include_str!("include/stdalign.h")),
+    ("stdarg.h", include_str!("include/stdarg.h")),
+    ("stdbool.h", include_str!("include/stdbool.h")),
+    ("stddef.h", include_str!("include/stddef.h")),
+    ("stdint.h", include_str!("include/stdint.h")),
+    ("stdnoreturn.h", include_str!("include/stdnoreturn.h")),
+];
+
+/// A `FileProvider` which adds the bundled headers to `files`.
+pub(super) struct WithBundledHeaders {
+    headers: MemoryFileSystem,
+    files: Box<dyn FileProvider>,
+}
+
+impl WithBundledHeaders {
+    pub(super) fn new(files: Box<dyn FileProvider>) -> WithBundledHeaders {
+        let mut headers = MemoryFileSystem::new();
+        for (name, contents) in HEADERS.iter() {
+            headers.insert(Path::new(BUNDLED_INCLUDE_DIR).join(name), contents);
+        }
+        WithBundledHeaders { headers, files }
+    }
+
+    fn provider(&self, path: &Path) -> &dyn FileProvider {
+        if path.starts_with(BUNDLED_INCLUDE_DIR) {
+            &self.headers
+        } else {
+            &*self.files
+        }
+    }
+}
+
+impl FileProvider for WithBundledHeaders {
+    fn is_file(&self, path: &Path) -> bool {
+        self.provider(path).is_file(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.provider(path).read(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        self.provider(path).canonicalize(path)
+    }
+}
+
+/// An integer type of the target.
+struct IntType {
+    signed: &'static str,
+    unsigned: &'static str,
+    bytes: u64,
+    // suffix of the constants of the signed type, the unsigned one adds `U`
+    suffix: &'static str,
+}
+
+// from the smallest to the largest, the first one of a size is preferred
+const INT_TYPES: [IntType; 5] = [
+    IntType {
+        signed: "signed char",
+        unsigned: "unsigned char",
+        bytes: X86_64_CHAR_BYTES,
+        suffix: "",
+    },
+    IntType {
+        signed: "short int",
+        unsigned: "short unsigned int",
+        bytes: X86_64_SHORT_BYTES,
+        suffix: "",
+    },
+    IntType {
+        signed: "int",
+        unsigned: "unsigned int",
+        bytes: X86_64_INT_BYTES,
+        suffix: "",
+    },
+    IntType {
+        signed: "long int",
+        unsigned: "long unsigned int",
+        bytes: X86_64_LONG_BYTES,
+        suffix: "L",
+    },
+    IntType {
+        signed: "long long int",
+        unsigned: "long long unsigned int",
+        bytes: X86_64_LONG_LONG_BYTES,
+        suffix: "LL",
+    },
+];
+
+impl IntType {
+    fn max(&self) -> String {
+        format!(
+            "0x7f{}{}",
+            "ff".repeat(self.bytes as usize - 1),
+            self.suffix
+        )
+    }
+
+    fn unsigned_suffix(&self) -> String {
+        // the types smaller than int are promoted to int
+        if self.bytes < X86_64_INT_BYTES {
+            String::new()
+        } else {
+            format!("U{}", self.suffix)
+        }
+    }
+
+    fn unsigned_max(&self) -> String {
+        format!(
+            "0x{}{}",
+            "ff".repeat(self.bytes as usize),
+            self.unsigned_suffix()
+        )
+    }
+
+    /// `__NAME_TYPE__` and `__NAME_MAX__` of the signed type and of the unsigned one,
+    /// which is named `UNAME`.
+    fn macros(&self, name: &str) -> Vec<(String, String)> {
+        vec![
+            (format!("__{}_TYPE__", name), self.signed.to_string()),
+            (format!("__U{}_TYPE__", name), self.unsigned.to_string()),
+            (format!("__{}_MAX__", name), self.max()),
+            (format!("__U{}_MAX__", name), self.unsigned_max()),
+        ]
+    }
+
+    /// `__NAME_C(c)` of the signed type and of the unsigned one.
+    fn constant_macros(&self, name: &str) -> Vec<(String, String)> {
+        let paste = |suffix: &str| {
+            if suffix.is_empty() {
+                "c".to_string()
+            } else {
+                format!("c ## {}", suffix)
+            }
+        };
+        vec![
+            (format!("__{}_C(c)", name), paste(self.suffix)),
+            (format!("__U{}_C(c)", name), paste(&self.unsigned_suffix())),
+        ]
+    }
+}
+
+/// The smallest type with at least `bytes` bytes.
+fn least(bytes: u64) -> &'static IntType {
+    INT_TYPES.iter().find(|t| t.bytes >= bytes).unwrap()
+}
+
+/// The predefined macros which describe the integer types to the bundled headers.
+pub(super) fn type_macros() -> Vec<(String, String)> {
+    let int = least(X86_64_INT_BYTES);
+    let pointer = least(X86_64_POINTER_BYTES);
+    let largest = INT_TYPES.iter().fold(&INT_TYPES[0], |largest, t| {
+        if t.bytes > largest.bytes {
+            t
+        } else {
+            largest
+        }
+    });
+
+    let mut res = vec![
+        ("__SCHAR_MAX__".to_string(), INT_TYPES[0].max()),
+        ("__SHRT_MAX__".to_string(), INT_TYPES[1].max()),
+        ("__INT_MAX__".to_string(), INT_TYPES[2].max()),
+        ("__LONG_MAX__".to_string(), INT_TYPES[3].max()),
+        ("__LONG_LONG_MAX__".to_string(), INT_TYPES[4].max()),
+    ];
+    for bits in [8, 16, 32, 64].iter() {
+        let bytes = bits / 8;
+        let exact = least(bytes);
+        if exact.bytes == bytes {
+            res.extend(exact.macros(&format!("INT{}", bits)));
+            res.extend(exact.constant_macros(&format!("INT{}", bits)));
+        }
+        res.extend(exact.macros(&format!("INT_LEAST{}", bits)));
+        // like gcc, the fast types are at least as large as a register
+        let fast = if bytes == 1 {
+            exact
+        } else {
+            least(bytes.max(X86_64_POINTER_BYTES))
+        };
+        res.extend(fast.macros(&format!("INT_FAST{}", bits)));
+    }
+    res.extend(pointer.macros("INTPTR"));
+    res.extend(largest.macros("INTMAX"));
+    res.extend(largest.constant_macros("INTMAX"));
+    res.extend(vec![
+        ("__SIZE_TYPE__".to_string(), pointer.unsigned.to_string()),
+        ("__SIZE_MAX__".to_string(), pointer.unsigned_max()),
+        ("__PTRDIFF_TYPE__".to_string(), pointer.signed.to_string()),
+        ("__PTRDIFF_MAX__".to_string(), pointer.max()),
+        ("__WCHAR_TYPE__".to_string(), int.signed.to_string()),
+        ("__WCHAR_MAX__".to_string(), int.max()),
+        (
+            "__WCHAR_MIN__".to_string(),
+            "(-__WCHAR_MAX__ - 1)".to_string(),
+        ),
+        ("__WINT_TYPE__".to_string(), int.unsigned.to_string()),
+        ("__WINT_MAX__".to_string(), int.unsigned_max()),
+        ("__WINT_MIN__".to_string(), "0U".to_string()),
+        ("__SIG_ATOMIC_TYPE__".to_string(), int.signed.to_string()),
+        ("__SIG_ATOMIC_MAX__".to_string(), int.max()),
+        (
+            "__SIG_ATOMIC_MIN__".to_string(),
+            "(-__SIG_ATOMIC_MAX__ - 1)".to_string(),
+        ),
+        ("__CHAR16_TYPE__".to_string(), least(2).unsigned.to_string()),
+        ("__CHAR32_TYPE__".to_string(), least(4).unsigned.to_string()),
+    ]);
+    res
+}
@@ -0,0 +1,25 @@
+/* stdarg.h: variable arguments, C11 7.16 */
+
+/* no typedef under __CRUST_NO_TYPEDEF, see stdint.h */
+#if !defined(__CRUST_GNUC_VA_LIST) && !defined(__CRUST_NO_TYPEDEF)
+#define __CRUST_GNUC_VA_LIST
+typedef __builtin_va_list __gnuc_va_list;
+#endif
+
+/* a library header may ask for __gnuc_va_list only with __need___va_list */
+#ifdef __need___va_list
+#undef __need___va_list
+#elif !defined(__CRUST_STDARG_H)
+#define __CRUST_STDARG_H
+
+#ifndef __CRUST_NO_TYPEDEF
+typedef __gnuc_va_list va_list;
+#endif
+
+#define va_start(ap, param) __builtin_va_start(ap, param)
+#define va_arg(ap, type) __builtin_va_arg(ap, type)
+#define va_end(ap) __builtin_va_end(ap)
+#if __STDC_VERSION__ >= 199901L
+#define va_copy(dest, src) __builtin_va_copy(dest, src)
+#endif
+#endif
@@ -0,0 +1,54 @@
+/* stddef.h: common definitions, C11 7.19 */
+
+/* a library header may ask for some of the definitions only with __need_size_t,
+   __need_ptrdiff_t, __need_wchar_t, __need_wint_t and __need_NULL */
+#if !defined(__need_size_t) && !defined(__need_ptrdiff_t) && !defined(__need_wchar_t) && \
+    !defined(__need_wint_t) && !defined(__need_NULL)
+#ifndef __CRUST_STDDEF_H
+#define __CRUST_STDDEF_H
+#define __need_size_t
+#define __need_ptrdiff_t
+#define __need_wchar_t
+#define __need_NULL
+
+#define offsetof(type, member) ((__SIZE_TYPE__)&((type *)0)->member)
+
+/* the typedefs are left out under __CRUST_NO_TYPEDEF, see stdint.h */
+#if __STDC_VERSION__ >= 201112L && !defined(__CRUST_NO_TYPEDEF)
+typedef struct {
+    long long __max_align_ll;
+    long double __max_align_ld;
+} max_align_t;
+#endif
+#endif
+#endif
+
+#if defined(__need_size_t) && !defined(__CRUST_SIZE_T) && !defined(__CRUST_NO_TYPEDEF)
+#define __CRUST_SIZE_T
+typedef __SIZE_TYPE__ size_t;
+#endif
+#undef __need_size_t
+
+#if defined(__need_ptrdiff_t) && !defined(__CRUST_PTRDIFF_T) && !defined(__CRUST_NO_TYPEDEF)
+#define __CRUST_PTRDIFF_T
+typedef __PTRDIFF_TYPE__ ptrdiff_t;
+#endif
+#undef __need_ptrdiff_t
+
+#if defined(__need_wchar_t) && !defined(__CRUST_WCHAR_T) && !defined(__CRUST_NO_TYPEDEF)
+#define __CRUST_WCHAR_T
+typedef __WCHAR_TYPE__ wchar_t;
+#endif
+#undef __need_wchar_t
+
+#if defined(__need_wint_t) && !defined(__CRUST_WINT_T) && !defined(__CRUST_NO_TYPEDEF)
+#define __CRUST_WINT_T
+typedef __WINT_TYPE__ wint_t;
+#endif
+#undef __need_wint_t
+
+#ifdef __need_NULL
+#undef NULL
+#define NULL ((void *)0)
+#endif
+#undef __need_NULL
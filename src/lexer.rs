@@ -15,11 +15,8 @@
 // lexer.rs: lexer for c11 tokens.
 //           some situations should be added later.
 // ------------------------------------------------------------------------
-// TODO: 1. add token information for error message.
-//       2. seperate each TokType to their type, now just a global type TokType.
-//       3. add some check in lexer for enum and typedef.
-//       4. add floating point number support.
-//       5. number with postfix.
+// TODO: 1. seperate each TokType to their type, now just a global type TokType.
+//       2. add some check in lexer for enum and typedef.
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum TokType {
@@ -48,9 +45,11 @@ pub enum TokType {
     ExclusiveOr,  // ^
     Mod,          // %
     IDENTIFIER(String),
-    IConstant(i64),
-    FConstant(f64),
-    StringLiteral(String, String),
+    IConstant(u128, IntSuffix),
+    FConstant(f64, FloatSuffix),
+    CharConstant(i64, Encoding),
+    // the code units without the null character, the encoding and the tag
+    StringLiteral(Vec<u32>, Encoding, String),
     FuncName,    // __func__
     SIZEOF,      // sizeof
     PtrOp,       // ->
@@ -122,9 +121,172 @@ pub enum TokType {
     NORETURN,
     StaticAssert,
     ThreadLocal,
+    BuiltinVaList, // __builtin_va_list
 }
 
-use std::sync::atomic;
+/// The suffix of an integer constant and whether the constant is decimal, which
+/// together give the list of its possible types, C11 6.4.4.1p5.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub struct IntSuffix {
+    pub unsigned: bool, // `u` or `U`
+    pub length: IntLength,
+    pub decimal: bool,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum IntLength {
+    #[default]
+    None,
+    Long,     // `l` or `L`
+    LongLong, // `ll` or `LL`
+}
+
+/// The encoding prefix of a character constant or a string literal, C11 6.4.4.4 and 6.4.5.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum Encoding {
+    #[default]
+    Char, // no prefix
+    Utf8,   // u8
+    Wide,   // L, wchar_t
+    Char16, // u, char16_t
+    Char32, // U, char32_t
+}
+
+impl Encoding {
+    /// The encoding given by the prefix of `s` and the length of the prefix.
+    fn prefix(s: &str) -> (Encoding, usize) {
+        if s.starts_with("u8") {
+            return (Encoding::Utf8, 2);
+        }
+        match s.chars().next() {
+            Some('L') => (Encoding::Wide, 1),
+            Some('u') => (Encoding::Char16, 1),
+            Some('U') => (Encoding::Char32, 1),
+            _ => (Encoding::Char, 0),
+        }
+    }
+
+    /// The largest value of a code unit.
+    fn max(self) -> u32 {
+        match self {
+            Encoding::Char | Encoding::Utf8 => 0xff,
+            Encoding::Char16 => 0xffff,
+            Encoding::Wide | Encoding::Char32 => 0xffff_ffff,
+        }
+    }
+
+    /// Append the code units of `c` to `units`, the char strings are in UTF-8.
+    fn encode(self, c: char, units: &mut Vec<u32>) {
+        match self {
+            Encoding::Char | Encoding::Utf8 => {
+                units.extend(c.encode_utf8(&mut [0; 4]).bytes().map(u32::from))
+            }
+            Encoding::Char16 => {
+                units.extend(c.encode_utf16(&mut [0; 2]).iter().map(|&u| u32::from(u)))
+            }
+            Encoding::Wide | Encoding::Char32 => units.push(c as u32),
+        }
+    }
+
+    /// The text of the code `units`, the invalid ones are replaced by U+FFFD.
+    pub fn to_string_lossy(self, units: &[u32]) -> String {
+        match self {
+            Encoding::Char | Encoding::Utf8 => {
+                let bytes: Vec<u8> = units.iter().map(|&u| u as u8).collect();
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+            Encoding::Char16 => {
+                let units: Vec<u16> = units.iter().map(|&u| u as u16).collect();
+                String::from_utf16_lossy(&units)
+            }
+            Encoding::Wide | Encoding::Char32 => units
+                .iter()
+                .map(|&u| char::from_u32(u).unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+        }
+    }
+}
+
+/// The suffix of a floating constant, which gives its type, C11 6.4.4.2p4.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FloatSuffix {
+    None, // double
+    F,    // float
+    L,    // long double
+}
+
+/// Where a token is in the source files.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Span {
+    /// index of the file in `Tokens::files`
+    pub file: usize,
+    /// byte offset of the token in the file
+    pub offset: usize,
+    pub line: usize,
+    /// counted in bytes from 1, like gcc
+    pub column: usize,
+    /// the macro expansion which produced the token, the location is the one of the
+    /// macro name
+    pub expansion: Option<Arc<cpp::Expansion>>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Token {
+    pub kind: TokType,
+    pub span: Span,
+    /// the spelling of the token in the source, for the diagnostics
+    pub text: String,
+}
+
+/// The tokens of a translation unit and the files they come from.
+///
+/// It derefs to the slice of tokens, the files are only needed to print the locations.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Tokens {
+    toks: Vec<Token>,
+    /// the names of the files, in the order they are first seen
+    pub files: Vec<String>,
+}
+
+impl Tokens {
+    /// `file:line:column` of `span`.
+    pub fn location(&self, span: &Span) -> String {
+        format!("{}:{}:{}", self.files[span.file], span.line, span.column)
+    }
+
+    /// The message `msg` at `span`, followed by the macro expansions the token comes from.
+    pub fn diagnostic(&self, span: &Span, msg: &str) -> String {
+        format!(
+            "{}: {}{}",
+            self.location(span),
+            msg,
+            cpp::expansion_notes(span.expansion.as_deref())
+        )
+    }
+
+    /// The index of the file `name` in `files`, it is added if it is new.
+    fn file_id(&mut self, name: &str) -> usize {
+        match self.files.iter().position(|f| f == name) {
+            Some(id) => id,
+            None => {
+                self.files.push(name.to_string());
+                self.files.len() - 1
+            }
+        }
+    }
+}
+
+impl Deref for Tokens {
+    type Target = [Token];
+
+    fn deref(&self) -> &[Token] {
+        &self.toks
+    }
+}
+
+use crate::cpp::{self, PPTokenKind};
+use std::ops::Deref;
+use std::sync::{atomic, Arc};
 
 static LABEL_COUNTER: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
 fn gen_string_tag() -> String {
@@ -134,472 +296,541 @@ fn gen_string_tag() -> String {
     label
 }
 
-pub fn lex(input: &str) -> Result<Vec<TokType>, String> {
-    let mut result = Vec::new();
+fn keyword_or_identifier(s: &str) -> TokType {
+    match s {
+        "auto" => TokType::AUTO,
+        "break" => TokType::BREAK,
+        "case" => TokType::CASE,
+        "char" => TokType::CHAR,
+        "const" => TokType::CONST,
+        "continue" => TokType::CONTINUE,
+        "default" => TokType::DEFAULT,
+        "do" => TokType::DO,
+        "double" => TokType::DOUBLE,
+        "else" => TokType::ELSE,
+        "enum" => TokType::ENUM,
+        "extern" => TokType::EXTERN,
+        "float" => TokType::FLOAT,
+        "for" => TokType::FOR,
+        "goto" => TokType::GOTO,
+        "if" => TokType::IF,
+        "inline" => TokType::INLINE,
+        "int" => TokType::INT,
+        "long" => TokType::LONG,
+        "register" => TokType::REGISTER,
+        "restrict" => TokType::RESTRICT,
+        "return" => TokType::RETURN,
+        "short" => TokType::SHORT,
+        "signed" => TokType::SIGNED,
+        "sizeof" => TokType::SIZEOF,
+        "static" => TokType::STATIC,
+        "struct" => TokType::STRUCT,
+        "switch" => TokType::SWITCH,
+        "typedef" => TokType::TYPEDEF,
+        "union" => TokType::UNION,
+        "unsigned" => TokType::UNSIGNED,
+        "void" => TokType::VOID,
+        "volatile" => TokType::VOLATILE,
+        "while" => TokType::WHILE,
+        "_Alignas" => TokType::ALIGNAS,
+        "_Alignof" => TokType::ALIGNOF,
+        "_Atomic" => TokType::ATOMIC,
+        "_Bool" => TokType::BOOL,
+        "_Complex" => TokType::COMPLEX,
+        "_Generic" => TokType::GENERIC,
+        "_Imaginary" => TokType::IMAGINARY,
+        "_Noreturn" => TokType::NORETURN,
+        "_Static_assert" => TokType::StaticAssert,
+        "_Thread_local" => TokType::ThreadLocal,
+        "__func__" => TokType::FuncName,
+        "__builtin_va_list" => TokType::BuiltinVaList,
+        _ => TokType::IDENTIFIER(s.to_string()),
+    }
+}
+
+fn lex_punctuator(s: &str) -> Result<TokType, String> {
+    let tok = match cpp::digraph(s).unwrap_or(s) {
+        "{" => TokType::LBrace,
+        "}" => TokType::RBrace,
+        "(" => TokType::LParen,
+        ")" => TokType::RParen,
+        "[" => TokType::LBracket,
+        "]" => TokType::RBracket,
+        ";" => TokType::Semicolon,
+        "=" => TokType::Assign,
+        "<" => TokType::Lt,
+        ">" => TokType::Gt,
+        "-" => TokType::Minus,
+        "~" => TokType::Tilde,
+        "!" => TokType::Exclamation,
+        "+" => TokType::Plus,
+        "*" => TokType::Multi,
+        "/" => TokType::Splash,
+        ":" => TokType::Colon,
+        "?" => TokType::QuestionMark,
+        "," => TokType::Comma,
+        "." => TokType::Dot,
+        "&" => TokType::SingleAnd,
+        "|" => TokType::InclusiveOr,
+        "^" => TokType::ExclusiveOr,
+        "%" => TokType::Mod,
+        "->" => TokType::PtrOp,
+        "++" => TokType::IncOp,
+        "--" => TokType::DecOp,
+        "<<" => TokType::LeftOp,
+        ">>" => TokType::RightOp,
+        "<=" => TokType::LeOp,
+        ">=" => TokType::GeOp,
+        "==" => TokType::EqOp,
+        "!=" => TokType::NeOp,
+        "&&" => TokType::AndOp,
+        "||" => TokType::OrOp,
+        "*=" => TokType::MulAssign,
+        "/=" => TokType::DivAssign,
+        "%=" => TokType::ModAssign,
+        "+=" => TokType::AddAssign,
+        "-=" => TokType::SubAssign,
+        "<<=" => TokType::LeftAssign,
+        ">>=" => TokType::RightAssign,
+        "&=" => TokType::AndAssign,
+        "^=" => TokType::XorAssign,
+        "|=" => TokType::OrAssign,
+        "..." => TokType::ELLIPSIS,
+        // `#` and `##` are only meaningful to the preprocessor
+        _ => return Err(format!("unexpected character `{}`", s)),
+    };
+    Ok(tok)
+}
 
-    let mut it = input.chars().peekable();
+fn lex_number(s: &str) -> Result<TokType, String> {
+    let hex = s.starts_with("0x") || s.starts_with("0X");
+    if (hex && s.contains(['.', 'p', 'P'])) || (!hex && s.contains(['.', 'e', 'E'])) {
+        return lex_float(s);
+    }
+    lex_integer(s)
+}
+
+/// An integer constant, C11 6.4.4.1, `0b` binary constants are a GNU extension.
+fn lex_integer(s: &str) -> Result<TokType, String> {
+    let (radix, start) = if s.starts_with("0x") || s.starts_with("0X") {
+        (16, 2)
+    } else if s.starts_with("0b") || s.starts_with("0B") {
+        (2, 2)
+    } else if s.starts_with('0') {
+        (8, 1)
+    } else {
+        (10, 0)
+    };
+    // the digits of the other radixes are taken too, so that `09` is an invalid digit
+    // and not an invalid suffix
+    let end = start + digits_len(&s[start..], radix.max(10));
+    let digits = &s[start..end];
+    if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+        let name = if radix == 8 { "octal" } else { "binary" };
+        return Err(format!(
+            "invalid digit \"{}\" in {} constant {}",
+            c, name, s
+        ));
+    }
+    // `0x` and `0b` need digits, else the `x` or `b` is taken as a suffix like gcc does
+    let suffix = if digits.is_empty() && radix != 8 {
+        &s[1..]
+    } else {
+        &s[end..]
+    };
+    let mut res = int_suffix(suffix)
+        .ok_or_else(|| format!("invalid suffix \"{}\" on integer constant {}", suffix, s))?;
+    res.decimal = radix == 10;
+
+    let mut value: u128 = 0;
+    for c in digits.chars() {
+        value = value * radix as u128 + c.to_digit(radix).unwrap() as u128;
+        // no type can hold it, C11 6.4.4.1p6
+        if value > u64::MAX as u128 {
+            return Err(format!("integer constant {} is too large for its type", s));
+        }
+    }
+    Ok(TokType::IConstant(value, res))
+}
+
+/// The integer suffix `s`, `u` comes before or after the length.
+fn int_suffix(s: &str) -> Option<IntSuffix> {
+    let (unsigned, length) = if let Some(rest) = s.strip_prefix(['u', 'U']) {
+        (true, rest)
+    } else if let Some(rest) = s.strip_suffix(['u', 'U']) {
+        (true, rest)
+    } else {
+        (false, s)
+    };
+    let length = match length {
+        "" => IntLength::None,
+        "l" | "L" => IntLength::Long,
+        "ll" | "LL" => IntLength::LongLong,
+        _ => return None,
+    };
+    Some(IntSuffix {
+        unsigned,
+        length,
+        decimal: false,
+    })
+}
+
+/// The number of bytes at the start of `s` which are digits of `radix`.
+fn digits_len(s: &str, radix: u32) -> usize {
+    s.find(|c: char| !c.is_digit(radix)).unwrap_or(s.len())
+}
 
-    while let Some(&c) = it.peek() {
-        match c {
-            '"' => {
-                it.next();
-                let mut s = "".to_string();
-                loop {
-                    let &c = it.peek().unwrap();
-                    if c == '"' {
-                        break;
+/// A floating constant, C11 6.4.4.2.
+///
+/// The value is correctly rounded to the type of the constant, a long double is only as
+/// precise as a double for now.
+fn lex_float(s: &str) -> Result<TokType, String> {
+    let hex = s.starts_with("0x") || s.starts_with("0X");
+    let (radix, start) = if hex { (16, 2) } else { (10, 0) };
+    // the significand, with its optional `.`
+    let int_len = digits_len(&s[start..], radix);
+    let mut end = start + int_len;
+    let mut frac_len = 0;
+    if s[end..].starts_with('.') {
+        frac_len = digits_len(&s[end + 1..], radix);
+        end += 1 + frac_len;
+    }
+    if int_len == 0 && frac_len == 0 {
+        return Err(format!("invalid floating constant {}", s));
+    }
+    // the exponent, which is required by the hexadecimal form
+    let exp_start = end;
+    let exp_marker = if hex { ['p', 'P'] } else { ['e', 'E'] };
+    if s[end..].starts_with(exp_marker) {
+        end += 1;
+        if s[end..].starts_with(['+', '-']) {
+            end += 1;
+        }
+        let exp_len = digits_len(&s[end..], 10);
+        if exp_len == 0 {
+            return Err(format!("exponent has no digits in {}", s));
+        }
+        end += exp_len;
+    } else if hex {
+        return Err(format!(
+            "hexadecimal floating constant {} requires an exponent",
+            s
+        ));
+    }
+
+    let suffix = match &s[end..] {
+        "" => FloatSuffix::None,
+        "f" | "F" => FloatSuffix::F,
+        "l" | "L" => FloatSuffix::L,
+        other => {
+            return Err(format!(
+                "invalid suffix \"{}\" on floating constant {}",
+                other, s
+            ))
+        }
+    };
+    let value = if hex {
+        let digits = s[start..exp_start].replace('.', "");
+        // saturate the exponents which do not fit, the value is 0 or infinite anyway
+        let exp = s[exp_start + 1..end].parse::<i32>().unwrap_or(
+            if s[exp_start + 1..].starts_with('-') {
+                i32::MIN / 2
+            } else {
+                i32::MAX / 2
+            },
+        );
+        let exp = exp.saturating_sub(4 * frac_len as i32);
+        match suffix {
+            FloatSuffix::F => hex_float(&digits, exp, 24, -126) as f32 as f64,
+            _ => hex_float(&digits, exp, 53, -1022),
+        }
+    } else {
+        match suffix {
+            // rounded to float directly, rounding to double first could round twice
+            FloatSuffix::F => s[..end].parse::<f32>().map(f64::from),
+            _ => s[..end].parse::<f64>(),
+        }
+        .map_err(|_| format!("invalid floating constant {}", s))?
+    };
+    // the constants out of range are infinite, lex_tokens warns about them like gcc
+    Ok(TokType::FConstant(value, suffix))
+}
+
+/// The value of the hexadecimal `digits` times 2 to the power of `exp`, rounded to the
+/// nearest value with a significand of `precision` bits, ties to even. `min_exp` is the
+/// exponent of the smallest normal value, below it the values are subnormal.
+fn hex_float(digits: &str, exp: i32, precision: i32, min_exp: i32) -> f64 {
+    // the first 30 significant digits are kept in `significand`, which is more than
+    // any precision needs, `sticky` tells if one of the other digits is not zero
+    let mut significand: u128 = 0;
+    let mut exp = exp;
+    let mut sticky = false;
+    for d in digits.trim_start_matches('0').chars() {
+        let d = d.to_digit(16).unwrap() as u128;
+        if significand >> 116 == 0 {
+            significand = significand << 4 | d;
+        } else {
+            sticky |= d != 0;
+            exp = exp.saturating_add(4);
+        }
+    }
+    if significand == 0 {
+        return 0.0;
+    }
+    let bits = 128 - significand.leading_zeros() as i32;
+    // the value is 1.xxx times 2 to the power of `top`
+    let top = exp.saturating_add(bits - 1);
+    let precision = precision - (min_exp - top).max(0);
+    if precision < 0 {
+        // less than half of the smallest subnormal
+        return 0.0;
+    }
+    let shift = bits - precision;
+    if shift <= 0 {
+        return ldexp(significand as f64, exp);
+    }
+    let (kept, rest, half) = if shift >= 128 {
+        (0, significand, 1u128 << 127)
+    } else {
+        (
+            significand >> shift,
+            significand & ((1 << shift) - 1),
+            1 << (shift - 1),
+        )
+    };
+    let round_up = rest > half || (rest == half && (sticky || kept & 1 == 1));
+    let kept = if round_up { kept + 1 } else { kept };
+    ldexp(kept as f64, exp.saturating_add(shift))
+}
+
+/// `x` times 2 to the power of `exp`, which is exact unless the result is out of range.
+fn ldexp(x: f64, exp: i32) -> f64 {
+    let mut x = x;
+    let mut exp = exp;
+    while exp > 1000 && x.is_finite() && x != 0.0 {
+        x *= 2f64.powi(1000);
+        exp -= 1000;
+    }
+    while exp < -1000 && x != 0.0 {
+        x *= 2f64.powi(-1000);
+        exp += 1000;
+    }
+    x * 2f64.powi(exp)
+}
+
+/// The code units of the body of a character constant or a string literal, the escape
+/// sequences are replaced by the values they stand for, translation phase 5.
+fn decode(body: &str, encoding: Encoding) -> Result<Vec<u32>, String> {
+    let mut units = Vec::new();
+    let mut it = body.chars().peekable();
+    while let Some(c) = it.next() {
+        if c != '\\' {
+            encoding.encode(c, &mut units);
+            continue;
+        }
+        // the tokenizer keeps a `\` in a literal with the character after it, but `#` may
+        // leave one at the end
+        let e = match it.next() {
+            Some(e) => e,
+            None => return Err("invalid string literal: trailing `\\`".to_string()),
+        };
+        let value = match e {
+            'a' => 0x07, // Alert (Beep, Bell) (added in C89)
+            'b' => 0x08, // Backspace
+            'e' => 0x1B, // escape character, GNU extension
+            'f' => 0x0C, // Formfeed Page Break
+            'n' => 0x0A, // Newline (Line Feed)
+            'r' => 0x0D, // Carriage Return
+            't' => 0x09, // Horizontal Tab
+            'v' => 0x0B, // Vertical Tab
+            '\\' | '\'' | '"' | '?' => e as u32,
+            '0'..='7' => {
+                let mut value = e.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match it.peek().and_then(|c| c.to_digit(8)) {
+                        Some(d) => value = value * 8 + d,
+                        None => break,
                     }
-                    s.push(c);
                     it.next();
                 }
-                result.push(TokType::StringLiteral(s, gen_string_tag()));
-                it.next();
-            }
-            '\'' => {
-                // try parse a char
-                it.next(); // skip '
-                let &c = it.peek().unwrap();
-                if c == '\'' {
-                    return Err(format!("Error: empty character constant"));
-                }
-                if c == '\\' {
-                    it.next();
-                    let &c = it.peek().unwrap();
-                    match c {
-                        'a' => {
-                            result.push(TokType::IConstant(0x07));
-                        } // Alert (Beep, Bell) (added in C89)
-                        'b' => {
-                            result.push(TokType::IConstant(0x08));
-                        } // Backspace
-                        'e' => {
-                            result.push(TokType::IConstant(0x1B));
-                        } // escape character
-                        'f' => {
-                            result.push(TokType::IConstant(0x0C));
-                        } // Formfeed Page Break
-                        'n' => {
-                            result.push(TokType::IConstant(0x0A));
-                        } // Newline (Line Feed)
-                        'r' => {
-                            result.push(TokType::IConstant(0x0D));
-                        } // Carriage Return
-                        't' => {
-                            result.push(TokType::IConstant(0x09));
-                        } // Horizontal Tab
-                        'v' => {
-                            result.push(TokType::IConstant(0x0B));
-                        } // Vertical Tab
-                        '\\' => {
-                            result.push(TokType::IConstant(0x5C));
-                        } // Backslash
-                        '\'' => {
-                            result.push(TokType::IConstant(0x27));
-                        } // Apostrophe or single quotation mark
-                        '\"' => {
-                            result.push(TokType::IConstant(0x22));
-                        } // Double quotation mark
-                        '?' => {
-                            result.push(TokType::IConstant(0x3F));
-                        } // question mark
-                        _ => {
-                            return Err(format!("unrecongnized character"));
-                        }
-                    }
-                    it.next();
-                    if it.peek().unwrap() != &'\'' {
-                        return Err(format!("Error: unmatched '"));
-                    }
-                    it.next();
-                } else {
-                    result.push(TokType::IConstant(c as i64));
-                    it.next(); // skip char
-                    it.next(); // skip '
+                if value > encoding.max() {
+                    return Err("octal escape sequence out of range".to_string());
                 }
+                value
             }
-            '0'...'9' => {
-                it.next();
-                let mut number = c
-                    .to_string()
-                    .parse::<i64>()
-                    .expect("The caller should have passed a digit.");
-
-                while let Some(Ok(digit)) = it.peek().map(|c| c.to_string().parse::<i64>()) {
-                    number = number * 10 + digit;
+            'x' => {
+                let mut value: Option<u64> = None;
+                while let Some(d) = it.peek().and_then(|c| c.to_digit(16)) {
+                    value = Some(value.unwrap_or(0).saturating_mul(16) | u64::from(d));
                     it.next();
                 }
-                result.push(TokType::IConstant(number));
-            }
-            'a'...'z' | 'A'...'Z' | '_' => {
-                it.next();
-                let mut s = String::new();
-                s.push(c);
-                while let Some(&tmp) = it.peek() {
-                    match tmp {
-                        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => {
-                            s.push(tmp);
-                            it.next();
-                        }
-                        _ => {
-                            break;
-                        }
+                match value {
+                    None => return Err("\\x used with no following hex digits".to_string()),
+                    Some(value) if value > u64::from(encoding.max()) => {
+                        return Err("hex escape sequence out of range".to_string())
                     }
+                    Some(value) => value as u32,
                 }
-                match s.as_ref() {
-                    "auto" => result.push(TokType::AUTO),
-                    "break" => result.push(TokType::BREAK),
-                    "case" => result.push(TokType::CASE),
-                    "char" => result.push(TokType::CHAR),
-                    "const" => result.push(TokType::CONST),
-                    "continue" => result.push(TokType::CONTINUE),
-                    "default" => result.push(TokType::DEFAULT),
-                    "do" => result.push(TokType::DO),
-                    "double" => result.push(TokType::DOUBLE),
-                    "else" => result.push(TokType::ELSE),
-                    "enum" => result.push(TokType::ENUM),
-                    "extern" => result.push(TokType::EXTERN),
-                    "float" => result.push(TokType::FLOAT),
-                    "for" => result.push(TokType::FOR),
-                    "goto" => result.push(TokType::GOTO),
-                    "if" => result.push(TokType::IF),
-                    "inline" => result.push(TokType::INLINE),
-                    "int" => result.push(TokType::INT),
-                    "long" => result.push(TokType::LONG),
-                    "register" => result.push(TokType::REGISTER),
-                    "restrict" => result.push(TokType::RESTRICT),
-                    "return" => result.push(TokType::RETURN),
-                    "short" => result.push(TokType::SHORT),
-                    "signed" => result.push(TokType::SIGNED),
-                    "sizeof" => result.push(TokType::SIZEOF),
-                    "static" => result.push(TokType::STATIC),
-                    "struct" => result.push(TokType::STRUCT),
-                    "switch" => result.push(TokType::SWITCH),
-                    "typedef" => result.push(TokType::TYPEDEF),
-                    "union" => result.push(TokType::UNION),
-                    "unsigned" => result.push(TokType::UNSIGNED),
-                    "void" => result.push(TokType::VOID),
-                    "volatile" => result.push(TokType::VOLATILE),
-                    "while" => result.push(TokType::WHILE),
-                    "_Alignas" => result.push(TokType::ALIGNAS),
-                    "_Alignof" => result.push(TokType::ALIGNOF),
-                    "_Atomic" => result.push(TokType::ATOMIC),
-                    "_Bool" => result.push(TokType::BOOL),
-                    "_Complex" => result.push(TokType::COMPLEX),
-                    "_Generic" => result.push(TokType::GENERIC),
-                    "_Imaginary" => result.push(TokType::IMAGINARY),
-                    "_Noreturn" => result.push(TokType::NORETURN),
-                    "_Static_assert" => result.push(TokType::StaticAssert),
-                    "_Thread_local" => result.push(TokType::ThreadLocal),
-                    "__func__" => result.push(TokType::FuncName),
-                    _ => result.push(TokType::IDENTIFIER(s)),
-                }
-            }
-            '(' => {
-                result.push(TokType::LParen);
-                it.next();
-            }
-            ')' => {
-                result.push(TokType::RParen);
-                it.next();
-            }
-            '{' => {
-                result.push(TokType::LBrace);
-                it.next();
-            }
-            '}' => {
-                result.push(TokType::RBrace);
-                it.next();
             }
-            '[' => {
-                result.push(TokType::LBracket);
-                it.next();
-            }
-            ']' => {
-                result.push(TokType::RBracket);
-                it.next();
-            }
-            ';' => {
-                result.push(TokType::Semicolon);
-                it.next();
-            }
-            '=' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::EqOp);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Assign);
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
+            // universal character names, C11 6.4.3
+            'u' | 'U' => {
+                let len = if e == 'u' { 4 } else { 8 };
+                let digits: String = it
+                    .clone()
+                    .take(len)
+                    .take_while(|c| c.is_ascii_hexdigit())
+                    .collect();
+                if digits.len() != len {
+                    return Err(format!(
+                        "incomplete universal character name \\{}{}",
+                        e, digits
+                    ));
                 }
-            }
-            '<' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            it.next();
-                            result.push(TokType::LeOp);
-                            it.next();
-                        }
-                        '<' => {
-                            it.next();
-                            match it.peek() {
-                                Some(tmp) => match tmp {
-                                    '=' => {
-                                        it.next();
-                                        result.push(TokType::LeftAssign); // <<=
-                                        it.next();
-                                    }
-                                    _ => {
-                                        result.push(TokType::LeftOp);
-                                        it.next();
-                                    }
-                                },
-                                _ => {
-                                    result.push(TokType::LeftOp);
-                                }
-                            }
-                        }
-                        _ => {
-                            result.push(TokType::Lt);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Lt);
-                    }
-                }
-            }
-            '>' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::GeOp);
-                            it.next();
-                        }
-                        '>' => {
-                            it.next();
-                            match it.peek() {
-                                Some(tmp) => match tmp {
-                                    '=' => {
-                                        result.push(TokType::RightAssign);
-                                        it.next();
-                                    }
-                                    _ => {
-                                        result.push(TokType::RightOp);
-                                    }
-                                },
-                                _ => {
-                                    result.push(TokType::RightOp);
-                                }
-                            }
-                        }
-                        _ => {
-                            result.push(TokType::Gt);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Gt);
-                    }
-                }
-            }
-            '-' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '-' => {
-                            result.push(TokType::DecOp);
-                            it.next();
-                        }
-                        '=' => {
-                            result.push(TokType::SubAssign);
-                            it.next();
-                        }
-                        '>' => {
-                            result.push(TokType::PtrOp);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Minus);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Minus);
-                    }
-                }
-            }
-            '~' => {
-                result.push(TokType::Tilde);
-                it.next();
-            }
-            '!' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::NeOp);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Exclamation);
-                        }
-                    },
-                    _ => return Err(format!("Can not peek next char")),
-                }
-            }
-            '+' => {
-                it.next();
-                match it.peek().unwrap() {
-                    '+' => {
-                        result.push(TokType::IncOp);
-                        it.next();
-                    }
-                    '=' => {
-                        result.push(TokType::AddAssign);
-                        it.next();
+                it.nth(len - 1);
+                let value = u32::from_str_radix(&digits, 16).unwrap();
+                match char::from_u32(value) {
+                    Some(c) if value >= 0xa0 || c == '$' || c == '@' || c == '`' => {
+                        encoding.encode(c, &mut units);
+                        continue;
                     }
                     _ => {
-                        result.push(TokType::Plus);
+                        return Err(format!(
+                            "\\{}{} is not a valid universal character",
+                            e, digits
+                        ))
                     }
                 }
             }
-            '*' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::MulAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Multi);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Multi);
-                    }
+            _ => return Err(format!("unknown escape sequence '\\{}'", e)),
+        };
+        units.push(value);
+    }
+    Ok(units)
+}
+
+/// A character constant, C11 6.4.4.4. The ones with more than one character are
+/// implementation-defined and not supported.
+fn lex_char_constant(s: &str) -> Result<TokType, String> {
+    let (encoding, start) = Encoding::prefix(s);
+    let units = decode(&s[start + 1..s.len() - 1], encoding)?;
+    let value = match (units.as_slice(), encoding) {
+        ([], _) => return Err("empty character constant".to_string()),
+        // char is signed like on x86-64, and wchar_t is int
+        ([c], Encoding::Char) => i64::from(*c as u8 as i8),
+        ([c], Encoding::Wide) => i64::from(*c as i32),
+        ([c], _) => i64::from(*c),
+        (_, Encoding::Char) => return Err(format!("multi-character character constant {}", s)),
+        _ => return Err(format!("character constant {} is too long for its type", s)),
+    };
+    Ok(TokType::CharConstant(value, encoding))
+}
+
+/// Concatenate the adjacent string `literals`, translation phases 5 and 6. The literals
+/// without prefix take the prefix of the others, C11 6.4.5p5.
+fn lex_string_literals(literals: &[&str]) -> Result<TokType, String> {
+    let mut encoding = Encoding::Char;
+    for lit in literals {
+        let (e, _) = Encoding::prefix(lit);
+        if e != Encoding::Char && encoding != Encoding::Char && e != encoding {
+            return Err("unsupported non-standard concatenation of string literals".to_string());
+        }
+        if e != Encoding::Char {
+            encoding = e;
+        }
+    }
+    let mut units = Vec::new();
+    for lit in literals {
+        let (_, start) = Encoding::prefix(lit);
+        units.extend(decode(&lit[start + 1..lit.len() - 1], encoding)?);
+    }
+    Ok(TokType::StringLiteral(units, encoding, gen_string_tag()))
+}
+
+/// Convert the preprocessing tokens from the preprocessor into tokens,
+/// this is translation phases 6 and 7.
+pub fn lex_tokens(toks: &[cpp::PPToken]) -> Result<Tokens, String> {
+    let mut result = Tokens::default();
+    // the file the tokens come from, set by the linemarkers
+    let mut file = "";
+    let mut file_id = None;
+
+    let mut it = toks.iter().peekable();
+    while let Some(tok) = it.next() {
+        let s = tok.text.as_str();
+        let t = match tok.kind {
+            PPTokenKind::Identifier => Ok(keyword_or_identifier(s)),
+            PPTokenKind::Number => lex_number(s),
+            PPTokenKind::CharConstant => lex_char_constant(s),
+            PPTokenKind::StringLiteral => {
+                let mut literals = vec![s];
+                while let Some(next) = it.next_if(|t| t.kind == PPTokenKind::StringLiteral) {
+                    literals.push(&next.text);
                 }
+                lex_string_literals(&literals)
             }
-            '%' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::ModAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Mod);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Mod);
-                    }
-                }
+            PPTokenKind::Punctuator => lex_punctuator(s),
+            PPTokenKind::Linemarker(_) => {
+                file = s;
+                file_id = None;
+                continue;
             }
-            '/' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::DivAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::Splash);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::Splash);
-                    }
-                }
+            // `#pragma` and `#ident` have no effect on the generated code yet
+            PPTokenKind::Directive => continue,
+            PPTokenKind::Other if s == "\"" || s == "'" => {
+                Err(format!("missing terminating {} character", s))
             }
-            '&' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '&' => {
-                            result.push(TokType::AndOp);
-                            it.next();
-                        }
-                        '=' => {
-                            result.push(TokType::AndAssign);
-                            it.next();
-                        }
-                        _ => {
-                            // & operator to get the address of a variable
-                            result.push(TokType::SingleAnd);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::SingleAnd);
-                    }
-                }
+            PPTokenKind::HeaderName | PPTokenKind::Other | PPTokenKind::Placemarker => {
+                Err(format!("unexpected character `{}`", s))
             }
-            '|' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '|' => {
-                            result.push(TokType::OrOp);
-                            it.next();
-                        }
-                        '=' => {
-                            result.push(TokType::OrAssign);
-                            it.next();
-                        }
-                        _ => {
-                            // now don't support bitwise or, so just return Err
-                            result.push(TokType::InclusiveOr);
-                        }
-                    },
-                    _ => {
-                        result.push(TokType::InclusiveOr);
+        };
+        let locate = |msg: &str| {
+            format!(
+                "{}:{}:{}: {}{}",
+                file,
+                tok.line,
+                tok.column,
+                msg,
+                cpp::expansion_notes(tok.expansion.as_deref())
+            )
+        };
+        match t {
+            Ok(kind) => {
+                if let TokType::FConstant(value, suffix) = kind {
+                    if value.is_infinite() {
+                        let ty = match suffix {
+                            FloatSuffix::None => "double",
+                            FloatSuffix::F => "float",
+                            FloatSuffix::L => "long double",
+                        };
+                        eprintln!(
+                            "warning: {}",
+                            locate(&format!(
+                                "floating constant exceeds range of '{}' [-Woverflow]",
+                                ty
+                            ))
+                        );
                     }
                 }
-            }
-            '^' => {
-                it.next();
-                match it.peek() {
-                    Some(tmp) => match tmp {
-                        '=' => {
-                            result.push(TokType::XorAssign);
-                            it.next();
-                        }
-                        _ => {
-                            result.push(TokType::ExclusiveOr);
-                        }
+                let file = *file_id.get_or_insert_with(|| result.file_id(file));
+                result.toks.push(Token {
+                    kind,
+                    span: Span {
+                        file,
+                        offset: tok.offset,
+                        line: tok.line,
+                        column: tok.column,
+                        expansion: tok.expansion.clone(),
                     },
-                    _ => {
-                        result.push(TokType::ExclusiveOr);
-                    }
-                }
-            }
-            '?' => {
-                result.push(TokType::QuestionMark);
-                it.next();
-            }
-            ':' => {
-                result.push(TokType::Colon);
-                it.next();
-            }
-            ',' => {
-                result.push(TokType::Comma);
-                it.next();
-            }
-            ' ' | '\n' | '\t' | '\r' => {
-                // skip
-                it.next();
-            }
-            _ => {
-                return Err(format!("unexpected character {}", c));
+                    text: s.to_string(),
+                })
             }
+            Err(err) => return Err(locate(&err)),
         }
     }
     Ok(result)
 }
+
+/// Lex the source text, which should have been preprocessed.
+pub fn lex(input: &str) -> Result<Tokens, String> {
+    lex_tokens(&cpp::tokenize(input))
+}
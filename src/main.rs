@@ -1,20 +1,17 @@
 //mod gen;
-mod ast;
-mod cpp;
-mod lexer;
-mod opts;
-mod parser;
-mod sema;
-mod symtable;
-
-use std::{error, fs};
-
-fn main() -> Result<(), Box<dyn error::Error>> {
-    let opts: opts::Opts = {
-        use structopt::StructOpt;
+use crust::{cpp, lexer, opts, parser, sema};
+use std::{error, fs, process};
+
+fn main() {
+    // the errors are printed with Display, they can span several lines
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
 
-        opts::Opts::from_args()
-    };
+fn run() -> Result<(), Box<dyn error::Error>> {
+    let opts = opts::Opts::parse();
 
     // TODO: allow support for multiple input files.
     //       Currently it tries to get the first input file and thats all
@@ -31,21 +28,45 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     }
 
     // 1. Preprocessing
-    let contents_after_cpp = cpp::cpp_driver(input_file_contents, input_file.clone())?;
+    let mut preprocessor = cpp::Preprocessor::new(opts.cpp_options())?;
+    let tokens_after_cpp = preprocessor.preprocess(input_file_contents, &input_file)?;
+
+    if let Some(file) = opts.dependency_file() {
+        let dependencies = opts.dependencies();
+        fs::write(
+            file,
+            preprocessor.dependencies().make_rule(
+                &opts.dependency_targets(),
+                dependencies.system(),
+                dependencies.phony(),
+            ),
+        )?;
+        if dependencies.only() {
+            return Ok(());
+        }
+    }
+
+    if opts.stop_stage().preprocess() && opts.dump_macros() {
+        fs::write(opts.output(), preprocessor.macro_definitions())?;
+        return Ok(());
+    }
 
     if opts.stop_stage().preprocess() {
-        fs::write(opts.output(), contents_after_cpp)?;
+        fs::write(
+            opts.output(),
+            cpp::detokenize(&tokens_after_cpp, !opts.no_linemarkers()),
+        )?;
         return Ok(());
     }
 
     // 2. lexing
-    let tokens = lexer::lex(&contents_after_cpp)?;
+    let tokens = lexer::lex_tokens(&tokens_after_cpp)?;
 
     if opts.crust_debug_flags().print_source_token() {
         println!("Tokens: \n");
         let mut inc = 0;
         for it in tokens.iter() {
-            print!("{}: [{:?}] ", inc, it);
+            print!("{}: [{:?}] ", inc, it.kind);
             inc += 1;
             if inc % 5 == 0 {
                 println!("");
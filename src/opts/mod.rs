@@ -1,4 +1,6 @@
-use std::path;
+use std::{env, ffi, path};
+
+use crate::cpp;
 
 mod arg_groups;
 
@@ -14,6 +16,25 @@ pub struct Opts {
     output: path::PathBuf,
     #[structopt(flatten)]
     stop_stage: StopStage,
+    /// Do not generate linemarkers in the output of -E
+    #[structopt(short = "-P")]
+    no_linemarkers: bool,
+    #[structopt(flatten)]
+    include_dirs: IncludeDirs,
+    /// The C standard, like c99, c11 or gnu11, the last one is used
+    #[structopt(long = "--std", number_of_values = 1)]
+    std: Vec<cpp::Standard>,
+    /// Replace the trigraphs, like ??= with #
+    #[structopt(long = "--trigraphs")]
+    trigraphs: bool,
+    #[structopt(flatten)]
+    macros: Macros,
+    #[structopt(flatten)]
+    dependencies: Dependencies,
+    /// Dump the macros with -E: -dM all of them at the end instead of the output,
+    /// -dD the definitions in the output, -dN only the macro names in the output
+    #[structopt(short = "-d", number_of_values = 1)]
+    dump: Vec<String>,
 }
 
 #[derive(structopt::StructOpt, Clone, Copy, Debug)]
@@ -30,6 +51,9 @@ pub struct CrustDebugFlags {
     /// Print the source file token.
     #[structopt(long = "--crust-print-source-token")]
     print_source_token: bool,
+    /// Print each macro expansion with its location
+    #[structopt(long = "--crust-trace-macros")]
+    trace_macros: bool,
 }
 
 #[derive(structopt::StructOpt, Clone, Copy, Debug)]
@@ -46,7 +70,130 @@ pub struct StopStage {
     preprocess: bool,
 }
 
+#[derive(structopt::StructOpt, Clone, Debug)]
+pub struct IncludeDirs {
+    /// Add the directory to the include search path
+    #[structopt(short = "-I", parse(from_os_str), number_of_values = 1)]
+    angled: Vec<path::PathBuf>,
+    /// Add the directory to the search path of `#include "..."` only
+    #[structopt(long = "--iquote", parse(from_os_str), number_of_values = 1)]
+    quote: Vec<path::PathBuf>,
+    /// Add the directory to the system include search path
+    #[structopt(long = "--isystem", parse(from_os_str), number_of_values = 1)]
+    system: Vec<path::PathBuf>,
+    /// Do not search the default system include directories
+    #[structopt(long = "--nostdinc")]
+    nostdinc: bool,
+}
+
+#[derive(structopt::StructOpt, Clone, Debug)]
+pub struct Macros {
+    /// Define a macro, as NAME or NAME=value
+    #[structopt(
+        short = "-D",
+        parse(from_str = "cpp::MacroOption::define"),
+        number_of_values = 1
+    )]
+    define: Vec<cpp::MacroOption>,
+    /// Undefine a macro
+    #[structopt(
+        short = "-U",
+        parse(from_str = "cpp::MacroOption::undef"),
+        number_of_values = 1
+    )]
+    undef: Vec<cpp::MacroOption>,
+    /// Include the file before the main file
+    #[structopt(long = "--include", parse(from_os_str), number_of_values = 1)]
+    include: Vec<path::PathBuf>,
+}
+
+#[derive(structopt::StructOpt, Clone, Debug)]
+pub struct Dependencies {
+    /// Write the make rule for the source file instead of the preprocessed output
+    #[structopt(long = "--M")]
+    all: bool,
+    /// Like -M, but leave out the system headers
+    #[structopt(long = "--MM")]
+    user: bool,
+    /// Write the make rule to a .d file as a side effect of the compilation
+    #[structopt(long = "--MD")]
+    all_side_effect: bool,
+    /// Like -MD, but leave out the system headers
+    #[structopt(long = "--MMD")]
+    user_side_effect: bool,
+    /// The file the make rule is written to
+    #[structopt(long = "--MF", parse(from_os_str))]
+    file: Option<path::PathBuf>,
+    /// The target of the make rule
+    #[structopt(long = "--MT", number_of_values = 1)]
+    targets: Vec<String>,
+    /// Add an empty rule for each header
+    #[structopt(long = "--MP")]
+    phony: bool,
+}
+
+// gcc spells these options with a single dash, which clap would take as a cluster of short
+// options, so they are rewritten to the `--` form before parsing. (name, takes a value)
+const SINGLE_DASH_LONG_OPTS: [(&str, bool); 13] = [
+    ("isystem", true),
+    ("iquote", true),
+    ("include", true),
+    ("nostdinc", false),
+    ("std", true),
+    ("trigraphs", false),
+    ("M", false),
+    ("MM", false),
+    ("MD", false),
+    ("MMD", false),
+    ("MF", true),
+    ("MT", true),
+    ("MP", false),
+];
+
+/// The command line arguments, with the gcc style long options rewritten for clap.
+pub fn args() -> Vec<ffi::OsString> {
+    env::args_os()
+        .map(|arg| {
+            let s = match arg.to_str() {
+                Some(s) if s.starts_with('-') && !s.starts_with("--") => &s[1..],
+                _ => return arg,
+            };
+            for (name, takes_value) in SINGLE_DASH_LONG_OPTS.iter() {
+                if s == *name {
+                    return format!("--{}", name).into();
+                }
+                // value attached to the option, like `-isystem/usr/include` or `-std=c99`
+                if *takes_value && s.starts_with(name) {
+                    let value = &s[name.len()..];
+                    let value = value.strip_prefix('=').unwrap_or(value);
+                    return format!("--{}={}", name, value).into();
+                }
+            }
+            arg
+        })
+        .collect()
+}
+
 impl Opts {
+    /// Parse the command line arguments.
+    pub fn parse() -> Opts {
+        use structopt::StructOpt;
+
+        let matches = Opts::clap().get_matches_from(args());
+        let mut opts = Opts::from_clap(&matches);
+        // `-D` and `-U` take effect in the order they are given, so they are merged
+        // into `define` by their position on the command line
+        let macros = &mut opts.macros;
+        let indices = |name| matches.indices_of(name).into_iter().flatten();
+        let mut merged: Vec<_> = indices("define")
+            .zip(macros.define.drain(..))
+            .chain(indices("undef").zip(macros.undef.drain(..)))
+            .collect();
+        merged.sort_by_key(|(idx, _)| *idx);
+        macros.define = merged.into_iter().map(|(_, option)| option).collect();
+        opts
+    }
+
     pub fn crust_debug_flags(&self) -> CrustDebugFlags {
         self.crust_debug_flags
     }
@@ -62,6 +209,92 @@ impl Opts {
     pub fn stop_stage(&self) -> StopStage {
         self.stop_stage
     }
+
+    pub fn no_linemarkers(&self) -> bool {
+        self.no_linemarkers
+    }
+
+    /// -dM, print the macros instead of the preprocessed output.
+    pub fn dump_macros(&self) -> bool {
+        self.dump.iter().any(|letters| letters.contains('M'))
+    }
+
+    /// The options of the preprocessor.
+    pub fn cpp_options(&self) -> cpp::Options {
+        let dump = if self.dump.iter().any(|letters| letters.contains('D')) {
+            cpp::MacroDump::Definitions
+        } else if self.dump.iter().any(|letters| letters.contains('N')) {
+            cpp::MacroDump::Names
+        } else {
+            cpp::MacroDump::None
+        };
+        let mut macros = self.macros.define.clone();
+        // the parser does not handle typedef yet, so the bundled headers leave out their
+        // typedefs when the output is compiled
+        if !self.stop_stage.preprocess() {
+            macros.insert(0, cpp::MacroOption::define("__CRUST_NO_TYPEDEF"));
+        }
+        cpp::Options {
+            include_paths: self.include_paths(),
+            std: self.std.last().copied().unwrap_or_default(),
+            macros,
+            includes: self.macros.include.clone(),
+            dump,
+            trace_macros: self.crust_debug_flags.trace_macros(),
+            trigraphs: self.trigraphs,
+        }
+    }
+
+    pub fn dependencies(&self) -> &Dependencies {
+        &self.dependencies
+    }
+
+    /// The file the make rule is written to, None without -M, -MM, -MD or -MMD.
+    pub fn dependency_file(&self) -> Option<path::PathBuf> {
+        let deps = &self.dependencies;
+        if let Some(file) = &deps.file {
+            return Some(file.clone()).filter(|_| deps.enabled());
+        }
+        if deps.all || deps.user {
+            Some(self.output.clone())
+        } else if deps.all_side_effect || deps.user_side_effect {
+            Some(self.output.with_extension("d"))
+        } else {
+            None
+        }
+    }
+
+    /// The targets of the make rule: those of -MT, otherwise the object file.
+    pub fn dependency_targets(&self) -> Vec<String> {
+        let deps = &self.dependencies;
+        if !deps.targets.is_empty() {
+            return deps.targets.clone();
+        }
+        // like gcc, the output file is the target of -MD when compiling to an object file
+        let side_effect = deps.all_side_effect || deps.user_side_effect;
+        if side_effect && self.stop_stage.assemble() {
+            return vec![self.output.display().to_string()];
+        }
+        let stem = self.input[0].file_stem().unwrap_or_default();
+        vec![format!("{}.o", stem.to_string_lossy())]
+    }
+
+    fn include_paths(&self) -> cpp::IncludePaths {
+        let dirs = &self.include_dirs;
+        let mut system = dirs.system.clone();
+        if !dirs.nostdinc {
+            system.extend(
+                cpp::DEFAULT_SYSTEM_INCLUDE_DIRS
+                    .iter()
+                    .map(path::PathBuf::from),
+            );
+        }
+        cpp::IncludePaths {
+            quote: dirs.quote.clone(),
+            angled: dirs.angled.clone(),
+            system,
+        }
+    }
 }
 
 impl CrustDebugFlags {
@@ -80,6 +313,30 @@ impl CrustDebugFlags {
     pub fn print_source_token(&self) -> bool {
         self.print_source_token
     }
+
+    pub fn trace_macros(&self) -> bool {
+        self.trace_macros
+    }
+}
+
+impl Dependencies {
+    fn enabled(&self) -> bool {
+        self.all || self.user || self.all_side_effect || self.user_side_effect
+    }
+
+    /// -M and -MM replace the preprocessed output with the make rule.
+    pub fn only(&self) -> bool {
+        self.all || self.user
+    }
+
+    /// The system headers are in the make rule, -M and -MD.
+    pub fn system(&self) -> bool {
+        !(self.user || self.user_side_effect)
+    }
+
+    pub fn phony(&self) -> bool {
+        self.phony
+    }
 }
 
 impl StopStage {
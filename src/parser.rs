@@ -20,6 +20,7 @@ use crate::ast::{ConstantType, NodeType, ParseNode};
 use crate::lexer;
 use crate::sema;
 use crate::symtable::{BaseType, TypeExpression};
+use std::cell::RefCell;
 
 // XXX: How to handle error message properly should be improved later
 //      and some uncommon situations support should be added.
@@ -27,40 +28,70 @@ use crate::symtable::{BaseType, TypeExpression};
 // ------------------------------------------------------------------------
 // helper function
 // ------------------------------------------------------------------------
-fn error_handler(expect: &str, toks: &lexer::TokType, pos: usize) -> String {
+fn error_handler(expect: &str, toks: &lexer::Tokens, pos: usize) -> String {
     // return a detailed error message.
     // now it could be simple, just print the token information
-    return format!("Expected `{}`, found {:?} at {}", expect, toks, pos);
+    return error_at(
+        toks,
+        pos,
+        &format!("Expected `{}`, found `{}`", expect, toks[pos].text),
+    );
 }
 
-fn check_tok(pos: usize, toks: &[lexer::TokType], expect: &lexer::TokType) -> Result<(), String> {
-    check_pos(pos, toks.len())?;
+thread_local! {
+    // the error at the furthest token, the parser backtracks over the errors of the
+    // alternatives it tries, so parser_driver reports this one instead
+    static FURTHEST_ERROR: RefCell<Option<(usize, String)>> = const { RefCell::new(None) };
+}
 
-    if &toks[pos] != expect {
-        return Err(format!("Expected: {:?}, found {:?}", expect, toks[pos]));
+// prefix the message with the location of the token at pos,
+// the errors at the end of the file are reported at the last token
+fn error_at(toks: &lexer::Tokens, pos: usize, msg: &str) -> String {
+    let err = match toks.get(pos).or(toks.last()) {
+        Some(tok) => toks.diagnostic(&tok.span, msg),
+        None => msg.to_string(),
+    };
+    FURTHEST_ERROR.with(|furthest| {
+        let mut furthest = furthest.borrow_mut();
+        if furthest.as_ref().is_none_or(|(at, _)| pos >= *at) {
+            *furthest = Some((pos, err.clone()));
+        }
+    });
+    err
+}
+
+fn check_tok(pos: usize, toks: &lexer::Tokens, expect: &lexer::TokType) -> Result<(), String> {
+    check_pos(pos, toks)?;
+
+    if &toks[pos].kind != expect {
+        return Err(error_at(
+            toks,
+            pos,
+            &format!("Expected: {:?}, found `{}`", expect, toks[pos].text),
+        ));
     }
 
     return Ok(());
 }
 
-fn check_pos(pos: usize, toks_len: usize) -> Result<(), String> {
-    if pos >= toks_len {
-        return Err(format!("out of token index"));
+fn check_pos(pos: usize, toks: &lexer::Tokens) -> Result<(), String> {
+    if pos >= toks.len() {
+        return Err(error_at(toks, pos, "unexpected end of file"));
     }
     return Ok(());
 }
 
-fn p_identifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_identifier(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::IDENTIFIER(val) => {
             let mut cur_node = ParseNode::new(NodeType::Identifier(val.to_string()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Identifier(val.to_string()));
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("identifier", &toks[pos], pos));
+            return Err(error_handler("identifier", toks, pos));
         }
     }
 }
@@ -73,8 +104,8 @@ fn p_identifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	| generic_selection
 // 	;
 
-fn p_primary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_primary_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::PrimaryExpression);
     if let Ok((child_node, new_pos)) = p_identifier(toks, pos) {
@@ -102,28 +133,34 @@ fn p_primary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
         cur_node.child.push(child_node);
         return Ok((cur_node, new_pos));
     } else {
-        return Err(format!("Can not parse primary expression"));
+        return Err(error_at(toks, pos, "Can not parse primary expression"));
     }
 }
 
 // constant
-// 	: IConstant		/* includes character_constant */
+// 	: IConstant
+// 	| CharConstant
 // 	| FConstant
 // 	| EnumerationConstant	/* after it has been defined as such */
 // 	;
-fn p_constant(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_constant(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
-        lexer::TokType::IConstant(i_val) => {
-            let mut cur_node = ParseNode::new(NodeType::Constant(ConstantType::I64(*i_val)));
-            // cause if the value was assigned to int, we can easily cast long to int.
-            cur_node.type_exp = TypeExpression::new_val(BaseType::Long);
+    match &toks[pos].kind {
+        lexer::TokType::IConstant(i_val, suffix) => {
+            // the value has at most 64 bits, the type tells whether they are signed
+            let mut cur_node = ParseNode::new(NodeType::Constant(ConstantType::I64(*i_val as i64)));
+            cur_node.type_exp = sema::integer_constant_type(*i_val, *suffix);
+            Ok((cur_node, pos + 1))
+        }
+        lexer::TokType::CharConstant(c_val, encoding) => {
+            let mut cur_node = ParseNode::new(NodeType::Constant(ConstantType::I64(*c_val)));
+            cur_node.type_exp = sema::char_constant_type(*encoding);
             Ok((cur_node, pos + 1))
         }
-        lexer::TokType::FConstant(f_val) => {
+        lexer::TokType::FConstant(f_val, suffix) => {
             let mut cur_node = ParseNode::new(NodeType::Constant(ConstantType::F64(*f_val)));
-            cur_node.type_exp = TypeExpression::new_val(BaseType::Double);
+            cur_node.type_exp = sema::float_constant_type(*suffix);
             Ok((cur_node, pos + 1))
         }
         lexer::TokType::EnumerationConstant(e_val) => {
@@ -133,7 +170,7 @@ fn p_constant(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize),
             cur_node.type_exp = TypeExpression::new_val(BaseType::Long);
             Ok((cur_node, pos + 1))
         }
-        _ => Err(error_handler("constant", &toks[pos], pos)),
+        _ => Err(error_handler("constant", toks, pos)),
     }
 }
 
@@ -141,19 +178,16 @@ fn p_constant(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize),
 // 	: IDENTIFIER
 // 	;
 // TODO: should judge whether a identifier is a enumeration_constant in semantics_analyzer
-fn p_enumeration_constant(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
-    match &toks[pos] {
+fn p_enumeration_constant(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
+    match &toks[pos].kind {
         lexer::TokType::IDENTIFIER(name) => {
             let mut cur_node = ParseNode::new(NodeType::EnumerationConstant(name.to_string()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Identifier(name.to_string()));
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("identifier", &toks[pos], pos));
+            return Err(error_handler("identifier", toks, pos));
         }
     }
 }
@@ -162,21 +196,19 @@ fn p_enumeration_constant(
 // 	: StringLiteral
 // 	| FuncName
 // 	;
-fn p_string(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_string(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
-        lexer::TokType::StringLiteral(v, _tag) => {
-            let mut cur_node = ParseNode::new(NodeType::STRING(v.to_string()));
-            let len = v.len();
-            let mut t_exp = TypeExpression::new_val(BaseType::Array(len));
-            t_exp.val.push(BaseType::Char);
-            cur_node.type_exp = t_exp;
+    match &toks[pos].kind {
+        lexer::TokType::StringLiteral(v, encoding, _tag) => {
+            let mut cur_node = ParseNode::new(NodeType::STRING(v.clone(), *encoding));
+            cur_node.type_exp = sema::string_literal_type(v.len(), *encoding);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::FuncName => {
             // FIXME: cause now there's no semantic analyzer, so just pass the literal
-            let mut cur_node = ParseNode::new(NodeType::STRING("__func_name__".to_string()));
+            let name = "__func_name__".bytes().map(u32::from).collect();
+            let mut cur_node = ParseNode::new(NodeType::STRING(name, lexer::Encoding::Char));
             let len = "__func_name__".len();
             let mut t_exp = TypeExpression::new_val(BaseType::Array(len));
             t_exp.val.push(BaseType::Char);
@@ -184,7 +216,7 @@ fn p_string(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), S
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("String literal", &toks[pos], pos));
+            return Err(error_handler("String literal", toks, pos));
         }
     }
 }
@@ -193,20 +225,20 @@ fn p_string(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), S
 // 	: GENERIC '(' assignment_expression ',' generic_assoc_list ')'
 // 	;
 // TODO: Add type system for this kind of node
-fn p_generic_selection(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_generic_selection(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::GenericSelection);
 
-    if toks[pos] != lexer::TokType::GENERIC {
-        return Err(error_handler("__Generic", &toks[pos], pos));
+    if toks[pos].kind != lexer::TokType::GENERIC {
+        return Err(error_handler("__Generic", toks, pos));
     }
     let pos = pos + 1;
 
     check_tok(pos, &toks, &lexer::TokType::LParen)?;
 
     let pos = pos + 1;
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
     let (child_node, pos) = p_assignment_expression(toks, pos)?;
     cur_node.child.push(child_node);
 
@@ -228,8 +260,8 @@ fn p_generic_selection(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // EBNF:
 // -> generic_association { ',' generic_association }
 // TODO: Add type system for this kind of node
-fn p_generic_assoc_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_generic_assoc_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::GenericAssocList);
 
@@ -261,26 +293,18 @@ fn p_generic_assoc_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	: type_name ':' assignment_expression
 // 	| DEFAULT ':' assignment_expression
 // TODO: Add type system for this kind of node
-fn p_generic_association(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    if pos >= toks.len() {
-        return Err(format!("out of token index"));
-    }
+fn p_generic_association(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::GenericAssociation);
     let mut pos = pos;
     if let Ok((child_node, tmp_pos)) = p_type_name(toks, pos) {
         pos = tmp_pos;
         cur_node.child.push(child_node);
-    } else if toks[pos] == lexer::TokType::DEFAULT {
+    } else if toks[pos].kind == lexer::TokType::DEFAULT {
         pos = pos + 1;
     } else {
-        return Err(format!(
-            "Can't find proper type name or default, found {:?} at {}",
-            toks[pos], pos
-        ));
+        return Err(error_handler("type name or default", toks, pos));
     }
 
     check_tok(pos, &toks, &lexer::TokType::Colon)?;
@@ -313,8 +337,8 @@ fn p_generic_association(
 // postfix_expression:
 //  pre { postfix_expression_post }
 // TODO: Need carefully review
-fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_postfix_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::PostfixExpression);
 
     if let Ok((child_node, pos)) = p_primary_expression(toks, pos) {
@@ -386,7 +410,7 @@ fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
             return Ok((cur_node, pos));
         }
     } else {
-        return Err(format!("Error parse postfix_expression"));
+        return Err(error_at(toks, pos, "Error parse postfix_expression"));
     }
 }
 
@@ -401,14 +425,15 @@ fn p_postfix_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // TODO: Need carefully review
 // TODO: Add type system for this kind of node
 fn p_postfix_expression_post(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::LBracket => {
-            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let mut cur_node =
+                ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
             let (child_node, pos) = p_expression(toks, pos)?;
             cur_node.type_exp = child_node.type_exp.clone();
@@ -418,7 +443,8 @@ fn p_postfix_expression_post(
             return Ok((cur_node, pos));
         }
         lexer::TokType::LParen => {
-            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let mut cur_node =
+                ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
             if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
                 let pos = pos + 1;
@@ -433,19 +459,20 @@ fn p_postfix_expression_post(
             }
         }
         lexer::TokType::Dot | lexer::TokType::PtrOp => {
-            let mut cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let mut cur_node =
+                ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
             let (child_node, pos) = p_identifier(toks, pos)?;
             cur_node.child.push(child_node);
             return Ok((cur_node, pos));
         }
         lexer::TokType::IncOp | lexer::TokType::DecOp => {
-            let cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].clone()));
+            let cur_node = ParseNode::new(NodeType::PostfixExpressionPost(toks[pos].kind.clone()));
             let pos = pos + 1;
             return Ok((cur_node, pos));
         }
         _ => {
-            return Err(format!("{:?} at {} is a postfix operator", toks[pos], pos));
+            return Err(error_handler("postfix operator", toks, pos));
         }
     }
 }
@@ -456,10 +483,10 @@ fn p_postfix_expression_post(
 // 	;
 // -> assignment_expression { ',' assignment_expression }
 fn p_argument_expression_list(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::ArgumentExpressionList);
 
@@ -503,12 +530,13 @@ fn p_argument_expression_list(
 // 	| SIZEOF '(' type_name ')'
 // 	| ALIGNOF '(' type_name ')'
 // 	;
-fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_unary_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
-    match toks[pos] {
+    match toks[pos].kind {
         lexer::TokType::IncOp | lexer::TokType::DecOp => {
-            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].kind.clone())));
             let pos = pos + 1;
             let (child_node, pos) = p_unary_expression(toks, pos)?;
             cur_node.type_exp = child_node.type_exp.clone();
@@ -518,7 +546,8 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         lexer::TokType::SIZEOF => {
             // assign the return type of sizeof() to size_t
             let pos = pos + 1;
-            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].kind.clone())));
             if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
                 let (child_node, pos) = p_type_name(toks, pos)?;
                 cur_node.type_exp = TypeExpression::new_val(BaseType::SizeT);
@@ -533,7 +562,8 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         }
         lexer::TokType::ALIGNOF => {
             // should return type size_t
-            let mut cur_node = ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::UnaryExpression(Some(toks[pos].kind.clone())));
             let pos = pos + 1;
             if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LParen) {
                 let pos = pos + 1;
@@ -542,7 +572,7 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
             } else {
-                return Err(error_handler("(", &toks[pos], pos));
+                return Err(error_handler("(", toks, pos));
             }
         }
         _ => {
@@ -583,7 +613,7 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
             } else {
-                return Err(format!("Can't parse unary_expression"));
+                return Err(error_at(toks, pos, "Can't parse unary_expression"));
             }
         }
     }
@@ -597,10 +627,10 @@ fn p_unary_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	| '~'
 // 	| '!'
 // 	;
-fn p_unary_operator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_unary_operator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     // need to match
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::Minus|
         lexer::TokType::SingleAnd| // '&', different with '&&' as TokType::And
         lexer::TokType::Multi|
@@ -608,10 +638,10 @@ fn p_unary_operator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
         lexer::TokType::Tilde |
         lexer::TokType::Plus => {
             // don't have type, just care about the operator type
-            return Ok((ParseNode::new(NodeType::UnaryOperator(toks[pos].clone())), pos + 1));
+            return Ok((ParseNode::new(NodeType::UnaryOperator(toks[pos].kind.clone())), pos + 1));
         }
         _ => {
-            return Err(error_handler("unary_operator", &toks[pos], pos));
+            return Err(error_handler("unary_operator", toks, pos));
         }
     }
 }
@@ -620,8 +650,8 @@ fn p_unary_operator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	: unary_expression
 // 	| '(' type_name ')' cast_expression
 // 	;
-fn p_cast_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_cast_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::CastExpression);
     if let Ok((child_node, pos)) = p_unary_expression(toks, pos) {
@@ -640,9 +670,10 @@ fn p_cast_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         let from_type = child_node.type_exp.clone();
 
         if sema::judge_cast(&to_type, &from_type) == false {
-            return Err(format!(
-                "Can not cast from {:?} to {:?}",
-                from_type, to_type
+            return Err(error_at(
+                toks,
+                pos,
+                &format!("Can not cast from {:?} to {:?}", from_type, to_type),
             ));
         }
 
@@ -650,7 +681,7 @@ fn p_cast_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Error parse cast_expression"));
+        return Err(error_at(toks, pos, "Error parse cast_expression"));
     }
 }
 
@@ -662,17 +693,17 @@ fn p_cast_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	;
 //   cast_expression { ('*' | '/' | '%') cast_expression }
 fn p_multiplicative_expression(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::MultiplicativeExpression);
     // exp -> multiplicative_expression
     let mut pos = pos;
     let (child_node, tmp_pos) = p_cast_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
     pos = tmp_pos;
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::Mod
         && *tok != lexer::TokType::Multi
         && *tok != lexer::TokType::Splash
@@ -689,6 +720,7 @@ fn p_multiplicative_expression(
         || *tok == lexer::TokType::Splash
     {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_cast_expression(toks, pos)?;
@@ -700,14 +732,18 @@ fn p_multiplicative_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -716,11 +752,8 @@ fn p_multiplicative_expression(
 // additive_expression
 // 	: multiplicative_expression { ("+" | "-") multiplicative_expression }
 // 	;
-fn p_additive_expression(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_additive_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::AdditiveExpression);
     // exp -> multiplicative_expression
@@ -728,7 +761,7 @@ fn p_additive_expression(
     let (child_node, tmp_pos) = p_multiplicative_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
     pos = tmp_pos;
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::Plus && *tok != lexer::TokType::Minus {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -740,6 +773,7 @@ fn p_additive_expression(
     let mut pos = pos;
     while *tok == lexer::TokType::Plus || *tok == lexer::TokType::Minus {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_multiplicative_expression(toks, pos)?;
@@ -750,14 +784,18 @@ fn p_additive_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -769,14 +807,14 @@ fn p_additive_expression(
 // 	| shift_expression RightOp additive_expression
 // 	;
 // -> additive_expression { (LeftOp | RightOp) additive_expression }
-fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_shift_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::ShiftExpression);
     // exp -> additive_expression
     let (child_node, pos) = p_additive_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::LeftOp && *tok != lexer::TokType::RightOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -788,6 +826,7 @@ fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
     let mut pos = pos;
     while *tok == lexer::TokType::LeftOp || *tok == lexer::TokType::RightOp {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_additive_expression(toks, pos)?;
@@ -798,14 +837,18 @@ fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -819,17 +862,14 @@ fn p_shift_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	| relational_expression GeOp shift_expression
 // 	;
 // -> shift_expression { ('<' | '>' | LeOp | GeOp) shift_expression }
-fn p_relational_expression(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_relational_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::RelationalExpression);
     // exp -> shift_expression
     let (child_node, pos) = p_shift_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::Lt
         && *tok != lexer::TokType::Gt
         && *tok != lexer::TokType::GeOp
@@ -848,6 +888,7 @@ fn p_relational_expression(
         || *tok == lexer::TokType::Gt
     {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_shift_expression(toks, pos)?;
@@ -858,14 +899,18 @@ fn p_relational_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -878,17 +923,14 @@ fn p_relational_expression(
 // 	| equality_expression NeOp relational_expression
 // 	;
 // -> relational_expression { (EqOp | NeOp) relational_expression }
-fn p_equality_expression(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_equality_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::EqualityExpression);
     // exp -> relational_expression
     let (child_node, pos) = p_relational_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::EqOp && *tok != lexer::TokType::NeOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -899,6 +941,7 @@ fn p_equality_expression(
     let mut pos = pos;
     while *tok == lexer::TokType::EqOp || *tok == lexer::TokType::NeOp {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_relational_expression(toks, pos)?;
@@ -909,14 +952,18 @@ fn p_equality_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -929,14 +976,14 @@ fn p_equality_expression(
 // 	;
 //  -> equality_expression { '&' equality_expression }
 // XXX:
-fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_and_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::AndExpression);
     // exp -> equality_expression
     let (child_node, pos) = p_equality_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::SingleAnd {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -947,6 +994,7 @@ fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
     let mut pos = pos;
     while *tok == lexer::TokType::SingleAnd {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_equality_expression(toks, pos)?;
@@ -957,14 +1005,18 @@ fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -977,16 +1029,16 @@ fn p_and_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	;
 //  -> and_expression { '^' and_expression }
 fn p_exclusive_or_expression(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::ExclusiveOrExpression);
     // exp -> and_expression
     let (child_node, pos) = p_and_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::ExclusiveOr {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -997,6 +1049,7 @@ fn p_exclusive_or_expression(
     let mut pos = pos;
     while *tok == lexer::TokType::ExclusiveOr {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_and_expression(toks, pos)?;
@@ -1007,14 +1060,18 @@ fn p_exclusive_or_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1027,16 +1084,16 @@ fn p_exclusive_or_expression(
 // 	;
 //  -> exclusive_or_expression { '|' exclusive_or_expression }
 fn p_inclusive_or_expression(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::InclusiveOrExpression);
     // exp -> exclusive_or_expression
     let (child_node, pos) = p_exclusive_or_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::InclusiveOr {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -1047,6 +1104,7 @@ fn p_inclusive_or_expression(
     let mut pos = pos;
     while *tok == lexer::TokType::InclusiveOr {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_exclusive_or_expression(toks, pos)?;
@@ -1057,14 +1115,18 @@ fn p_inclusive_or_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1077,16 +1139,16 @@ fn p_inclusive_or_expression(
 // 	;
 //  -> inclusive_or_expression { AndOp inclusive_or_expression }
 fn p_logical_and_expression(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::LogicalAndExpression);
     // exp -> inclusive_or_expression
     let (child_node, pos) = p_inclusive_or_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::AndOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -1097,6 +1159,7 @@ fn p_logical_and_expression(
     let mut pos = pos;
     while *tok == lexer::TokType::AndOp {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_inclusive_or_expression(toks, pos)?;
@@ -1107,14 +1170,18 @@ fn p_logical_and_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1126,17 +1193,14 @@ fn p_logical_and_expression(
 // 	| logical_or_expression OrOp logical_and_expression
 // 	;
 //  -> logical_and_expression { OrOp logical_and_expression }
-fn p_logical_or_expression(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_logical_or_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::LogicalOrExpression);
     // exp -> logical_and_expression
     let (child_node, pos) = p_logical_and_expression(toks, pos)?;
     let mut l_type = child_node.type_exp.clone();
-    let mut tok = &toks[pos];
+    let mut tok = &toks[pos].kind;
     if *tok != lexer::TokType::OrOp {
         cur_node.type_exp = l_type;
         cur_node.child.push(child_node);
@@ -1147,6 +1211,7 @@ fn p_logical_or_expression(
     let mut pos = pos;
     while *tok == lexer::TokType::OrOp {
         let mut bincur_node = ParseNode::new(NodeType::BinaryExpression(tok.clone()));
+        let op_pos = pos;
         pos = pos + 1;
         let op = tok.clone();
         let (next_child_node, tmp_pos) = p_logical_and_expression(toks, pos)?;
@@ -1157,14 +1222,18 @@ fn p_logical_or_expression(
         if let (true, combine_type) = sema::judge_combine_type(&l_type, &r_type, &op) {
             bincur_node.type_exp = combine_type;
         } else {
-            return Err(format!(
-                "can not use type: {:?} to {:?} type {:?}, ",
-                l_type, op, r_type
+            return Err(error_at(
+                toks,
+                op_pos,
+                &format!(
+                    "can not use type: {:?} to {:?} type {:?}, ",
+                    l_type, op, r_type
+                ),
             ));
         }
         child_node = bincur_node;
         l_type = child_node.type_exp.clone();
-        tok = &toks[pos];
+        tok = &toks[pos].kind;
     }
     cur_node.type_exp = child_node.type_exp.clone();
     cur_node.child.push(child_node);
@@ -1176,14 +1245,14 @@ fn p_logical_or_expression(
 // 	| logical_or_expression '?' expression ':' conditional_expression
 // 	;
 fn p_conditional_expression(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
     // XXX: should make sure expression and conditional_expression are the same type.
     //      the final conditional expression type would be expression type,
     //      and also have to make sure logical_or_expression can be converted to int or bool
 
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::ConditionalExpression);
     if let Ok((child_node, pos)) = p_logical_or_expression(toks, pos) {
@@ -1209,10 +1278,11 @@ fn p_conditional_expression(
                 &child_node.type_exp,
                 &TypeExpression::new_val(BaseType::Char),
             ) {
-
             } else {
-                return Err(format!(
-                    "Conditional Expression doesn't have logical expression"
+                return Err(error_at(
+                    toks,
+                    pos,
+                    "Conditional Expression doesn't have logical expression",
                 ));
             }
             cur_node.child.push(child_node);
@@ -1229,8 +1299,10 @@ fn p_conditional_expression(
             // TODO: actually they don't need to have same type, but need to be able to convert to the same type.
             //       which is the type of the left side of assignment.
             if sema::judge_type_same(&l_type, &r_type) == false {
-                return Err(format!(
-                    "Two option expression in Teneray Expression has different type"
+                return Err(error_at(
+                    toks,
+                    pos,
+                    "Two option expression in Teneray Expression has different type",
                 ));
             }
             cur_node.type_exp = child_node.type_exp.clone();
@@ -1241,7 +1313,7 @@ fn p_conditional_expression(
             return Ok((cur_node, pos));
         }
     } else {
-        return Err(format!("Error parse logical_or_expressiong"));
+        return Err(error_at(toks, pos, "Error parse logical_or_expressiong"));
     }
 }
 
@@ -1250,11 +1322,8 @@ fn p_conditional_expression(
 // 	| unary_expression assignment_operator assignment_expression
 // 	;
 
-fn p_assignment_expression(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_assignment_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::AssignmentExpression);
     if let Ok((child_node1, pos1)) = p_unary_expression(toks, pos) {
         if let Ok((child_node2, pos2)) = p_assignment_operator(toks, pos1) {
@@ -1300,13 +1369,10 @@ fn p_assignment_expression(
 // 	| XorAssign
 // 	| OrAssign
 // 	;
-fn p_assignment_operator(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_assignment_operator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::Assign
         | lexer::TokType::MulAssign
         | lexer::TokType::DivAssign
@@ -1319,12 +1385,12 @@ fn p_assignment_operator(
         | lexer::TokType::XorAssign
         | lexer::TokType::OrAssign => {
             return Ok((
-                ParseNode::new(NodeType::AssignmentOperator(toks[pos].clone())),
+                ParseNode::new(NodeType::AssignmentOperator(toks[pos].kind.clone())),
                 pos + 1,
             ));
         }
         _ => {
-            return Err(error_handler("Assignment operator", &toks[pos], pos));
+            return Err(error_handler("Assignment operator", toks, pos));
         }
     }
 }
@@ -1334,8 +1400,8 @@ fn p_assignment_operator(
 // 	| expression ',' assignment_expression
 // 	;
 //  -> assignment_expression { ',' assignment_expression }
-fn p_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::Expression);
 
@@ -1375,11 +1441,8 @@ fn p_expression(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // constant_expression
 // 	: conditional_expression	/* with constraints */
 // 	;
-fn p_constant_expression(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_constant_expression(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::ConstantExpression);
 
     let (child_node, pos) = p_conditional_expression(toks, pos)?;
@@ -1393,8 +1456,8 @@ fn p_constant_expression(
 // 	| declaration_specifiers init_declarator_list ';'
 // 	| static_assert_declaration
 // 	;
-fn p_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_declaration(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Declaration);
     if let Ok((child_node, pos)) = p_declaration_specifiers(toks, pos) {
         if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
@@ -1413,7 +1476,7 @@ fn p_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                return Err(error_handler(";", &toks[pos], pos));
+                return Err(error_handler(";", toks, pos));
             }
         }
     } else if let Ok((child_node, pos)) = p_static_assert_declaration(toks, pos) {
@@ -1421,7 +1484,7 @@ fn p_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Can't parse declaration"));
+        return Err(error_at(toks, pos, "Can't parse declaration"));
     }
 }
 
@@ -1438,10 +1501,10 @@ fn p_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
 // 	| alignment_specifier
 // 	;
 fn p_declaration_specifiers(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::DeclarationSpecifiers);
 
@@ -1506,7 +1569,7 @@ fn p_declaration_specifiers(
             return Ok((cur_node, pos));
         }
     } else {
-        return Err(format!("Can't parse declaration_specifiers"));
+        return Err(error_at(toks, pos, "Can't parse declaration_specifiers"));
     }
 }
 
@@ -1516,11 +1579,8 @@ fn p_declaration_specifiers(
 // 	;
 //  -> init_declarator { ',' init_declarator }
 
-fn p_init_declarator_list(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_init_declarator_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::InitDeclaratorList);
 
@@ -1565,8 +1625,8 @@ fn p_init_declarator_list(
 // 	: declarator '=' initializer
 // 	| declarator
 // 	;
-fn p_init_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_init_declarator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::InitDeclarator);
 
     if let Ok((child_node, pos)) = p_declarator(toks, pos) {
@@ -1579,7 +1639,7 @@ fn p_init_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
             if sema::judge_type_same(&pre_type, &child_node.type_exp) {
                 // ok
             } else {
-                return Err(format!("init_declarator, can not assign"));
+                return Err(error_at(toks, pos, "init_declarator, can not assign"));
             }
 
             cur_node.type_exp = pre_type;
@@ -1591,7 +1651,7 @@ fn p_init_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
             return Ok((cur_node, pos));
         }
     } else {
-        return Err(format!("Can't parse init_declarator"));
+        return Err(error_at(toks, pos, "Can't parse init_declarator"));
     }
 }
 
@@ -1604,42 +1664,47 @@ fn p_init_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	| REGISTER
 // 	;
 fn p_storage_class_specifier(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::TYPEDEF => {
-            return Err(format!("Typedef is not supported in crust now"));
+            return Err(error_at(toks, pos, "Typedef is not supported in crust now"));
         }
         lexer::TokType::EXTERN => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Extern);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::STATIC => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Static);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::ThreadLocal => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::ThreadLocal);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::AUTO => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Auto);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::REGISTER => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Register);
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("storage_class_specifier", &toks[pos], pos));
+            return Err(error_handler("storage_class_specifier", toks, pos));
         }
     }
 }
@@ -1661,73 +1726,92 @@ fn p_storage_class_specifier(
 // 	| struct_or_union_specifier
 // 	| enum_specifier
 // 	| TypedefName		/* after it has been defined as such */
+// 	| BuiltinVaList		/* va_list of <stdarg.h> */
 // 	;
-fn p_type_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    match &toks[pos] {
+fn p_type_specifier(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    match &toks[pos].kind {
         lexer::TokType::VOID => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Void);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::CHAR => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Char);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::SHORT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Short);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::INT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Int);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::LONG => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Long);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::FLOAT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Float);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::DOUBLE => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Double);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::SIGNED => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Signed);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::UNSIGNED => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Unsigned);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::BOOL => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Bool);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::COMPLEX => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Complex);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::IMAGINARY => {
-            let mut cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Imaginary);
             return Ok((cur_node, pos + 1));
         }
+        lexer::TokType::BuiltinVaList => {
+            let mut cur_node =
+                ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
+            cur_node.type_exp = TypeExpression::new_val(BaseType::VaList);
+            return Ok((cur_node, pos + 1));
+        }
         lexer::TokType::TypedefName => {
             // XXX: now can not handle typedef
-            return Err(format!("Typedef is not supported in crust now"));
-            // let cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].clone())));
+            return Err(error_at(toks, pos, "Typedef is not supported in crust now"));
+            // let cur_node = ParseNode::new(NodeType::TypeSpecifier(Some(toks[pos].kind.clone())));
             // cur_node.type_exp = TypeExpression::new_val(BaseType::Typedef);
             // return Ok((cur_node, pos + 1));
         }
@@ -1746,7 +1830,7 @@ fn p_type_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
                 cur_node.child.push(child_node);
                 return Ok((cur_node, pos));
             } else {
-                return Err(format!("Error parse type specifier"));
+                return Err(error_at(toks, pos, "Error parse type specifier"));
             }
         }
     }
@@ -1758,10 +1842,10 @@ fn p_type_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	| struct_or_union IDENTIFIER
 // 	;
 fn p_struct_or_union_specifier(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::StructOrUnionSpecifier);
     let (child_node, pos) = p_struct_or_union(toks, pos)?;
     cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -1801,23 +1885,23 @@ fn p_struct_or_union_specifier(
 // 	: STRUCT
 // 	| UNION
 // 	;
-fn p_struct_or_union(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
-    match &toks[pos] {
+fn p_struct_or_union(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
+    match &toks[pos].kind {
         lexer::TokType::STRUCT => {
-            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Struct);
 
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::UNION => {
-            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::StructOrUnion(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Union);
 
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("struct or union", &toks[pos], pos));
+            return Err(error_handler("struct or union", toks, pos));
         }
     }
 }
@@ -1827,10 +1911,10 @@ fn p_struct_or_union(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	;
 //  -> struct_declaration { struct_declaration }
 fn p_struct_declaration_list(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::StructDeclarationList);
 
     let (child_node, pos) = p_struct_declaration(toks, pos)?;
@@ -1856,8 +1940,8 @@ fn p_struct_declaration_list(
 // 	| specifier_qualifier_list struct_declarator_list ';'
 // 	| static_assert_declaration
 // 	;
-fn p_struct_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_struct_declaration(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::StructDeclaration);
     if let Ok((child_node, pos)) = p_specifier_qualifier_list(toks, pos) {
         let pre_type = child_node.type_exp.clone();
@@ -1878,14 +1962,14 @@ fn p_struct_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
             let pos = pos + 1;
             return Ok((cur_node, pos));
         } else {
-            return Err(error_handler(";", &toks[pos], pos));
+            return Err(error_handler(";", toks, pos));
         }
     } else if let Ok((child_node, pos)) = p_static_assert_declaration(toks, pos) {
         cur_node.type_exp = child_node.type_exp.clone();
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Error parse struct declaration"));
+        return Err(error_at(toks, pos, "Error parse struct declaration"));
     }
 }
 // specifier_qualifier_list
@@ -1895,10 +1979,10 @@ fn p_struct_declaration(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	| type_qualifier
 // 	;
 fn p_specifier_qualifier_list(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::SpecifierQualifier);
     if let Ok((child_node, pos)) = p_type_specifier(toks, pos) {
         let pre_type = child_node.type_exp.clone();
@@ -1925,7 +2009,7 @@ fn p_specifier_qualifier_list(
             return Ok((cur_node, pos));
         }
     } else {
-        return Err(format!("Error parse specifier_qualifier_list"));
+        return Err(error_at(toks, pos, "Error parse specifier_qualifier_list"));
     }
 }
 // struct_declarator_list
@@ -1934,10 +2018,10 @@ fn p_specifier_qualifier_list(
 // 	;
 //  -> struct_declarator { ',' struct_declarator }
 fn p_struct_declarator_list(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::StructDeclaratorList);
 
@@ -1980,8 +2064,8 @@ fn p_struct_declarator_list(
 // 	| declarator ':' constant_expression
 // 	| declarator
 // 	;
-fn p_struct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_struct_declarator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::StructDeclarator);
     if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Colon) {
         let pos = pos + 1;
@@ -2015,8 +2099,8 @@ fn p_struct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // 	| ENUM IDENTIFIER
 // 	;
 // TODO: Add type system
-fn p_enum_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_enum_specifier(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     check_tok(pos, &toks, &lexer::TokType::ENUM)?;
     let pos = pos + 1;
     if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBrace) {
@@ -2035,13 +2119,13 @@ fn p_enum_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
                 let pos = pos + 1;
                 return Ok((cur_node, pos));
             } else {
-                return Err(error_handler("}", &toks[pos], pos));
+                return Err(error_handler("}", toks, pos));
             }
         } else {
-            return Err(error_handler("}", &toks[pos], pos));
+            return Err(error_handler("}", toks, pos));
         }
     } else {
-        match &toks[pos] {
+        match &toks[pos].kind {
             lexer::TokType::IDENTIFIER(name) => {
                 let mut cur_node = ParseNode::new(NodeType::EnumSpecifier(Some(name.to_string())));
                 let pos = pos + 1;
@@ -2059,17 +2143,17 @@ fn p_enum_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
                             let pos = pos + 1;
                             return Ok((cur_node, pos));
                         } else {
-                            return Err(error_handler("}", &toks[pos], pos));
+                            return Err(error_handler("}", toks, pos));
                         }
                     } else {
-                        return Err(error_handler("}", &toks[pos], pos));
+                        return Err(error_handler("}", toks, pos));
                     }
                 } else {
-                    return Err(error_handler("}", &toks[pos], pos));
+                    return Err(error_handler("}", toks, pos));
                 }
             }
             _ => {
-                return Err(error_handler("`{` or identifier", &toks[pos], pos));
+                return Err(error_handler("`{` or identifier", toks, pos));
             }
         }
     }
@@ -2080,8 +2164,8 @@ fn p_enum_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	| enumerator_list ',' enumerator
 // 	;
 //  -> enumerator { ',' enumerator }
-fn p_enumerator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_enumerator_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::EnumeratorList);
     let (child_node, pos) = p_enumerator(toks, pos)?; // if error, then out
@@ -2114,8 +2198,8 @@ fn p_enumerator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	: enumeration_constant '=' constant_expression
 // 	| enumeration_constant
 // 	;
-fn p_enumerator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_enumerator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Enumerator);
     let (child_node, pos) = p_enumeration_constant(toks, pos)?;
     let pre_type = child_node.type_exp.clone();
@@ -2144,7 +2228,11 @@ fn p_enumerator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
         ) {
             // ok
         } else {
-            return Err(format!("enumeration_constant can only assign to int"));
+            return Err(error_at(
+                toks,
+                pos,
+                "enumeration_constant can only assign to int",
+            ));
         }
 
         cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -2159,11 +2247,8 @@ fn p_enumerator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // atomic_type_specifier
 // 	: ATOMIC '(' type_name ')'
 // 	;
-fn p_atomic_type_specifier(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_atomic_type_specifier(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::AtomicTypeSpecifier);
 
     check_tok(pos, &toks, &lexer::TokType::ATOMIC)?;
@@ -2188,33 +2273,33 @@ fn p_atomic_type_specifier(
 // 	| VOLATILE
 // 	| ATOMIC
 // 	;
-fn p_type_qualifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
-    match &toks[pos] {
+fn p_type_qualifier(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
+    match &toks[pos].kind {
         lexer::TokType::CONST => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Const);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::RESTRICT => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Restrict);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::VOLATILE => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Volatile);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::ATOMIC => {
-            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::TypeQualifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Atomic);
             return Ok((cur_node, pos + 1));
         }
         _ => {
             return Err(error_handler(
                 "[const, restricted, volatile, atomic]",
-                &toks[pos],
+                toks,
                 pos,
             ));
         }
@@ -2224,22 +2309,22 @@ fn p_type_qualifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	: INLINE
 // 	| NORETURN
 // 	;
-fn p_function_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_function_specifier(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::INLINE => {
-            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Inline);
             return Ok((cur_node, pos + 1));
         }
         lexer::TokType::NORETURN => {
-            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::FunctionSpecifier(toks[pos].kind.clone()));
             cur_node.type_exp = TypeExpression::new_val(BaseType::Noreturn);
             return Ok((cur_node, pos + 1));
         }
         _ => {
-            return Err(error_handler("[inline, noreturn]", &toks[pos], pos));
+            return Err(error_handler("[inline, noreturn]", toks, pos));
         }
     }
 }
@@ -2248,11 +2333,8 @@ fn p_function_specifier(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	| ALIGNAS '(' constant_expression ')'
 // 	;
 // XXX: now just return type non expression
-fn p_alignment_specifier(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_alignment_specifier(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     check_tok(pos, &toks, &lexer::TokType::ALIGNAS)?;
     let pos = pos + 1;
@@ -2269,7 +2351,7 @@ fn p_alignment_specifier(
         cur_node.child.push(child_node);
         pos = tmp_pos;
     } else {
-        return Err(format!("Error parse alignment_specifier"));
+        return Err(error_at(toks, pos, "Error parse alignment_specifier"));
     }
 
     check_tok(pos, &toks, &lexer::TokType::RParen)?;
@@ -2281,8 +2363,8 @@ fn p_alignment_specifier(
 // 	: pointer direct_declarator
 // 	| direct_declarator
 // 	;
-fn p_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_declarator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Declarator);
     if let Ok((child_node, pos)) = p_direct_declarator(toks, pos) {
         cur_node.type_exp = child_node.type_exp.clone();
@@ -2296,7 +2378,7 @@ fn p_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Error parse declarator"));
+        return Err(error_at(toks, pos, "Error parse declarator"));
     }
 }
 
@@ -2321,8 +2403,8 @@ fn p_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 /// I combine all the postfix together in one ParseNode
 /// so if this root node has two child, then it has postfix,
 /// otherwise just IDENTIFIER or '(' declarator ')'
-fn p_direct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_direct_declarator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::DirectDeclarator);
     let mut pos = pos;
 
@@ -2339,7 +2421,7 @@ fn p_direct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
         cur_node.child.push(child_node);
         pos = tmp_pos;
     } else {
-        return Err(format!("Error parse direct_declarator"));
+        return Err(error_at(toks, pos, "Error parse direct_declarator"));
     }
 
     if let Ok((child_node, pos)) = p_direct_declarator_post_list(toks, pos) {
@@ -2356,10 +2438,10 @@ fn p_direct_declarator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // direct_declarator_post_list
 // : direct_declarator_post { direct_declarator_post }
 fn p_direct_declarator_post_list(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DirectDeclaratorPostList);
     let (child_node, pos) = p_direct_declarator_post(toks, pos)?;
     let pre_type = child_node.type_exp.clone();
@@ -2396,13 +2478,14 @@ fn p_direct_declarator_post_list(
 // 	| '[' type_qualifier_list assignment_expression ']'
 // 	| '[' type_qualifier_list ']'
 fn p_direct_declarator_post(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    check_pos(pos, toks)?;
+    match &toks[pos].kind {
         lexer::TokType::LParen => {
-            let mut cur_node = ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].clone()));
+            let mut cur_node =
+                ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].kind.clone()));
             let pos = pos + 1;
             if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
                 let pos = pos + 1;
@@ -2423,7 +2506,8 @@ fn p_direct_declarator_post(
             }
         }
         lexer::TokType::LBracket => {
-            let mut cur_node = ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].clone()));
+            let mut cur_node =
+                ParseNode::new(NodeType::DirectDeclaratorPost(toks[pos].kind.clone()));
             let pos = pos + 1;
             if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBracket) {
                 let pos = pos + 1;
@@ -2438,7 +2522,7 @@ fn p_direct_declarator_post(
             }
         }
         _ => {
-            return Err(error_handler("[ or (", &toks[pos], pos));
+            return Err(error_handler("[ or (", toks, pos));
         }
     }
 }
@@ -2448,8 +2532,8 @@ fn p_direct_declarator_post(
 // 	| '*' pointer
 // 	| '*'
 // 	;
-fn p_pointer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_pointer(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Pointer);
     check_tok(pos, &toks, &lexer::TokType::Multi)?;
     cur_node.type_exp = TypeExpression::new_val(BaseType::Pointer);
@@ -2478,11 +2562,8 @@ fn p_pointer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize),
 // 	| type_qualifier_list type_qualifier
 // 	;
 //  -> type_qualifier { type_qualifier }
-fn p_type_qualifier_list(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_type_qualifier_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::TypeQualifierList);
     let (child_node, pos) = p_type_qualifier(toks, pos)?;
     cur_node.type_exp.child.push(child_node.type_exp.clone());
@@ -2499,11 +2580,8 @@ fn p_type_qualifier_list(
 // 	: parameter_list ',' ELLIPSIS
 // 	| parameter_list
 // 	;
-fn p_parameter_type_list(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_parameter_type_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::ParameterTypeList(false)); // no extra variable
     let (child_node, pos) = p_parameter_list(toks, pos)?;
     cur_node.type_exp = child_node.type_exp.clone();
@@ -2525,8 +2603,8 @@ fn p_parameter_type_list(
 // 	| parameter_list ',' parameter_declaration
 // 	;
 //  -> parameter_declaration { ',' parameter_declaration }
-fn p_parameter_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_parameter_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::ParameterList);
     let (child_node, pos) = p_parameter_declaration(toks, pos)?; // if error, then out
@@ -2560,11 +2638,8 @@ fn p_parameter_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	| declaration_specifiers abstract_declarator
 // 	| declaration_specifiers
 // 	;
-fn p_parameter_declaration(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_parameter_declaration(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::ParameterDeclaration);
     let (c, pos) = p_declaration_specifiers(toks, pos)?;
     let declaration_specifiers_type = c.type_exp.clone();
@@ -2591,8 +2666,8 @@ fn p_parameter_declaration(
 // 	| identifier_list ',' IDENTIFIER
 // 	;
 //  -> IDENTIFIER { ',' IDENTIFIER }
-fn p_identifier_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_identifier_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node: ParseNode = ParseNode::new(NodeType::IdentifierList);
     let (child_node, pos) = p_identifier(toks, pos)?; // if error, then out
@@ -2632,8 +2707,8 @@ fn p_identifier_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	: specifier_qualifier_list abstract_declarator
 // 	| specifier_qualifier_list
 // 	;
-fn p_type_name(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_type_name(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::TypeName);
     let (child_node, pos) = p_specifier_qualifier_list(toks, pos)?;
     let specifier_qualifier_list_type = child_node.type_exp.clone();
@@ -2655,11 +2730,8 @@ fn p_type_name(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize)
 // 	| pointer
 // 	| direct_abstract_declarator
 // 	;
-fn p_abstract_declarator(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_abstract_declarator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut cur_node = ParseNode::new(NodeType::AbstractDeclarator);
 
@@ -2678,7 +2750,7 @@ fn p_abstract_declarator(
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Error parse abstract_declarator"));
+        return Err(error_at(toks, pos, "Error parse abstract_declarator"));
     }
 }
 
@@ -2709,10 +2781,10 @@ fn p_abstract_declarator(
 // direct_abstract_declarator_block { direct_abstract_declarator_block }
 
 fn p_direct_abstract_declarator(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DirectAbstractDeclarator);
     let (child_node, pos) = p_direct_abstract_declarator_block(toks, pos)?;
     let pre_type = child_node.type_exp.clone();
@@ -2747,14 +2819,15 @@ fn p_direct_abstract_declarator(
 // 	| '[' type_qualifier_list assignment_expression ']'
 // 	| '[' type_qualifier_list ']'
 fn p_direct_abstract_declarator_block(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
-    match &toks[pos] {
+    check_pos(pos, toks)?;
+    match &toks[pos].kind {
         lexer::TokType::LParen => {
-            let mut cur_node =
-                ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(
+                toks[pos].kind.clone(),
+            ));
             let pos = pos + 1;
             if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RParen) {
                 let pos = pos + 1;
@@ -2777,8 +2850,9 @@ fn p_direct_abstract_declarator_block(
             }
         }
         lexer::TokType::LBracket => {
-            let mut cur_node =
-                ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::DirectAbstractDeclaratorBlock(
+                toks[pos].kind.clone(),
+            ));
             let pos = pos + 1;
             if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::RBracket) {
                 let pos = pos + 1;
@@ -2793,7 +2867,7 @@ fn p_direct_abstract_declarator_block(
             }
         }
         _ => {
-            return Err(error_handler("( or [", &toks[pos], pos));
+            return Err(error_handler("( or [", toks, pos));
         }
     }
 }
@@ -2803,8 +2877,8 @@ fn p_direct_abstract_declarator_block(
 // 	| '{' initializer_list ',' '}'
 // 	| assignment_expression
 // 	;
-fn p_initializer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_initializer(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Initializer);
 
     if let Ok((child_node, pos)) = p_assignment_expression(toks, pos) {
@@ -2838,8 +2912,8 @@ fn p_initializer(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
 // -> pre {',' pre}
 // XXX: designation initializer should get type(initializer) as its type
 //      but need to add judge function to judge whether it's ok to assign
-fn p_initializer_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_initializer_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
     let mut pre_type;
 
@@ -2858,7 +2932,7 @@ fn p_initializer_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
         pos = tmp_pos;
         cur_node.child.push(child_node);
     } else {
-        return Err(format!("Error parse initializer_list"));
+        return Err(error_at(toks, pos, "Error parse initializer_list"));
     }
     cur_node.type_exp.child.push(pre_type.clone());
 
@@ -2897,8 +2971,8 @@ fn p_initializer_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // designation
 // 	: designator_list '='
 // 	;
-fn p_designation(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_designation(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Designation);
     let (child_node, pos) = p_designator_list(toks, pos)?;
     cur_node.type_exp = child_node.type_exp.clone();
@@ -2912,8 +2986,8 @@ fn p_designation(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usiz
 // 	| designator_list designator
 // 	;
 //  -> designator { designator }
-fn p_designator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_designator_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DesignatorList);
     let (child_node, pos) = p_designator(toks, pos)?;
     let pre_type = child_node.type_exp.clone();
@@ -2940,8 +3014,8 @@ fn p_designator_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	: '[' constant_expression ']'
 // 	| '.' IDENTIFIER
 // 	;
-fn p_designator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_designator(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Designator);
     if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::LBracket) {
         let pos = pos + 1;
@@ -2965,10 +3039,10 @@ fn p_designator(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	: StaticAssert '(' constant_expression ',' StringLiteral ')' ';'
 // 	;
 fn p_static_assert_declaration(
-    toks: &[lexer::TokType],
+    toks: &lexer::Tokens,
     pos: usize,
 ) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+    check_pos(pos, toks)?;
 
     check_tok(pos, &toks, &lexer::TokType::StaticAssert)?;
     let pos = pos + 1;
@@ -3000,8 +3074,8 @@ fn p_static_assert_declaration(
 // 	| iteration_statement
 // 	| jump_statement
 // 	;
-fn p_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_statement(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::Statement);
     if let Ok((child_node, pos)) = p_labeled_statement(toks, pos) {
         cur_node.type_exp = child_node.type_exp.clone();
@@ -3028,7 +3102,7 @@ fn p_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize)
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Error parse statement"));
+        return Err(error_at(toks, pos, "Error parse statement"));
     }
 }
 // labeled_statement
@@ -3036,10 +3110,10 @@ fn p_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize)
 // 	| CASE constant_expression ':' statement
 // 	| DEFAULT ':' statement
 // 	;
-fn p_labeled_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_labeled_statement(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::LabeledStatement("".to_string()));
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::IDENTIFIER(s) => {
             cur_node.entry = NodeType::LabeledStatement(s.to_string());
             let pos = pos + 1;
@@ -3073,7 +3147,7 @@ fn p_labeled_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
             return Ok((cur_node, pos));
         }
         _ => {
-            return Err(error_handler("label", &toks[pos], pos));
+            return Err(error_handler("label", toks, pos));
         }
     }
 }
@@ -3082,8 +3156,8 @@ fn p_labeled_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode
 // 	: '{' '}'
 // 	| '{'  block_item_list '}'
 // 	;
-fn p_compound_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_compound_statement(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::CompoundStatement);
     check_tok(pos, &toks, &lexer::TokType::LBrace)?;
     let pos = pos + 1;
@@ -3105,8 +3179,8 @@ fn p_compound_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNod
 // 	| block_item_list block_item
 // 	;
 //  -> block_item { block_item }
-fn p_block_item_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_block_item_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::BlockItemList);
     let (child_node, pos) = p_block_item(toks, pos)?;
     let pre_type = child_node.type_exp.clone();
@@ -3131,8 +3205,8 @@ fn p_block_item_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	: declaration
 // 	| statement
 // 	;
-fn p_block_item(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_block_item(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::BlockItem);
     if let Ok((child_node, pos)) = p_declaration(toks, pos) {
         cur_node.type_exp = child_node.type_exp.clone();
@@ -3143,7 +3217,7 @@ fn p_block_item(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
         cur_node.child.push(child_node);
         return Ok((cur_node, pos));
     } else {
-        return Err(format!("Error parse block_item"));
+        return Err(error_at(toks, pos, "Error parse block_item"));
     }
 }
 
@@ -3151,11 +3225,8 @@ fn p_block_item(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize
 // 	: ';'
 // 	| expression ';'
 // 	;
-fn p_expression_statement(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_expression_statement(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::ExpressionStatement);
     if let Ok(_) = check_tok(pos, &toks, &lexer::TokType::Semicolon) {
         let pos = pos + 1;
@@ -3176,14 +3247,11 @@ fn p_expression_statement(
 // 	| IF '(' expression ')' statement
 // 	| SWITCH '(' expression ')' statement
 // 	;
-fn p_selection_statement(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
-    match &toks[pos] {
+fn p_selection_statement(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
+    match &toks[pos].kind {
         lexer::TokType::IF => {
-            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
 
             check_tok(pos, &toks, &lexer::TokType::LParen)?;
@@ -3210,7 +3278,7 @@ fn p_selection_statement(
             }
         }
         lexer::TokType::SWITCH => {
-            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::SelectionStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
 
             check_tok(pos, &toks, &lexer::TokType::LParen)?;
@@ -3227,7 +3295,7 @@ fn p_selection_statement(
             return Ok((cur_node, pos));
         }
         _ => {
-            return Err(error_handler("[if, switch]", &toks[pos], pos));
+            return Err(error_handler("[if, switch]", toks, pos));
         }
     }
 }
@@ -3240,15 +3308,12 @@ fn p_selection_statement(
 // 	| FOR '(' declaration expression_statement ')' statement
 // 	| FOR '(' declaration expression_statement expression ')' statement
 // 	;
-fn p_iteration_statement(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
-    match &toks[pos] {
+fn p_iteration_statement(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
+    match &toks[pos].kind {
         lexer::TokType::WHILE => {
             // 	: WHILE '(' expression ')' statement
-            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
 
             check_tok(pos, &toks, &lexer::TokType::LParen)?;
@@ -3267,7 +3332,7 @@ fn p_iteration_statement(
             return Ok((cur_node, pos));
         }
         lexer::TokType::DO => {
-            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
             let (child_node, pos) = p_statement(toks, pos)?;
             cur_node.child.push(child_node);
@@ -3295,7 +3360,7 @@ fn p_iteration_statement(
             // 	| FOR '(' expression_statement expression_statement expression ')' statement
             // 	| FOR '(' declaration expression_statement ')' statement
             // 	| FOR '(' declaration expression_statement expression ')' statement
-            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].clone()));
+            let mut cur_node = ParseNode::new(NodeType::IterationStatement(toks[pos].kind.clone()));
             let pos = pos + 1;
             check_tok(pos, &toks, &lexer::TokType::LParen)?;
             let pos = pos + 1;
@@ -3351,11 +3416,11 @@ fn p_iteration_statement(
                     return Ok((cur_node, pos));
                 }
             } else {
-                return Err(format!("Error parse For"));
+                return Err(error_at(toks, pos, "Error parse For"));
             }
         }
         _ => {
-            return Err(error_handler("[while, do, for]", &toks[pos], pos));
+            return Err(error_handler("[while, do, for]", toks, pos));
         }
     }
 }
@@ -3367,14 +3432,14 @@ fn p_iteration_statement(
 // 	| RETURN ';'
 // 	| RETURN expression ';'
 // 	;
-fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_jump_statement(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
 
-    match &toks[pos] {
+    match &toks[pos].kind {
         lexer::TokType::GOTO => {
             let pos = pos + 1;
-            check_pos(pos, toks.len())?;
-            match &toks[pos] {
+            check_pos(pos, toks)?;
+            match &toks[pos].kind {
                 lexer::TokType::IDENTIFIER(var) => {
                     let mut cur_node = ParseNode::new(NodeType::JumpStatement(
                         "goto".to_string(),
@@ -3387,7 +3452,7 @@ fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
                     return Ok((cur_node, pos));
                 }
                 _ => {
-                    return Err(error_handler("identifier for goto ", &toks[pos], pos));
+                    return Err(error_handler("identifier for goto ", toks, pos));
                 }
             }
         }
@@ -3428,11 +3493,7 @@ fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
             }
         }
         _ => {
-            return Err(error_handler(
-                "[goto, continue, break, return]",
-                &toks[pos],
-                pos,
-            ));
+            return Err(error_handler("[goto, continue, break, return]", toks, pos));
         }
     }
 }
@@ -3441,11 +3502,8 @@ fn p_jump_statement(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, u
 // 	: function_definition
 // 	| declaration
 // 	;
-fn p_external_declaration(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_external_declaration(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::ExternalDeclaration);
     if let Ok((child_node, pos)) = p_function_definition(toks, pos) {
         cur_node.type_exp = child_node.type_exp.clone();
@@ -3463,11 +3521,8 @@ fn p_external_declaration(
 // 	: declaration_specifiers declarator declaration_list compound_statement
 // 	| declaration_specifiers declarator compound_statement
 // 	;
-fn p_function_definition(
-    toks: &[lexer::TokType],
-    pos: usize,
-) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_function_definition(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node = ParseNode::new(NodeType::FunctionDefinition);
     cur_node.type_exp = TypeExpression::new_val(BaseType::Function);
 
@@ -3501,8 +3556,8 @@ fn p_function_definition(
 // 	| declaration_list declaration
 // 	;
 //  -> declaration { declaration }
-fn p_declaration_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_declaration_list(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::DeclarationList);
     let (child_node, pos) = p_declaration(toks, pos)?;
     let pre_type = child_node.type_exp.clone();
@@ -3530,8 +3585,8 @@ fn p_declaration_list(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
 // 	| translation_unit external_declaration
 // 	;
 //  -> external_declaration { external_declaration }
-fn p_translation_unit(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode, usize), String> {
-    check_pos(pos, toks.len())?;
+fn p_translation_unit(toks: &lexer::Tokens, pos: usize) -> Result<(ParseNode, usize), String> {
+    check_pos(pos, toks)?;
     let mut cur_node: ParseNode = ParseNode::new(NodeType::TranslationUnit);
     let mut pos: usize = pos;
     loop {
@@ -3546,15 +3601,22 @@ fn p_translation_unit(toks: &[lexer::TokType], pos: usize) -> Result<(ParseNode,
     return Ok((cur_node, pos));
 }
 
-pub fn parser_driver(toks: &[lexer::TokType], c_src_name: &str) -> Result<ParseNode, String> {
-    let (cur_node, pos) = p_translation_unit(&toks, 0)?;
-    if pos == toks.len() {
-        return Ok(cur_node);
-    } else {
-        Err(format!(
-            "Parser drive fails to parse the file {}",
-            c_src_name
-        ))
+pub fn parser_driver(toks: &lexer::Tokens, c_src_name: &str) -> Result<ParseNode, String> {
+    FURTHEST_ERROR.with(|furthest| *furthest.borrow_mut() = None);
+    let res = p_translation_unit(&toks, 0);
+    let furthest = FURTHEST_ERROR.with(|furthest| furthest.borrow_mut().take());
+    match res {
+        Ok((cur_node, pos)) if pos == toks.len() => Ok(cur_node),
+        // report the error which got the furthest into the file
+        Ok((_, pos)) => match furthest {
+            Some((at, err)) if at >= pos => Err(err),
+            _ => Err(error_at(
+                toks,
+                pos,
+                &format!("Parser drive fails to parse the file {}", c_src_name),
+            )),
+        },
+        Err(err) => Err(furthest.map_or(err, |(_, err)| err)),
     }
 }
 
@@ -3581,7 +3643,12 @@ pub fn parser_pretty_printer(tree: &ParseNode, depth: usize) -> String {
             tree.type_exp.print()
         ),
         NodeType::Identifier(name) => format!("\n{}type: {:?}, name: {:?}", idt, tree.entry, name),
-        NodeType::STRING(val) => format!("\n{}type: {:?}, val: {}", idt, tree.entry, val),
+        NodeType::STRING(val, encoding) => format!(
+            "\n{}type: {:?}, val: {}",
+            idt,
+            tree.entry,
+            encoding.to_string_lossy(val)
+        ),
         NodeType::PostfixExpressionPost(punc) => {
             format!("\n{}type: {:?}, punc: {:?}", idt, tree.entry, punc)
         }
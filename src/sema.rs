@@ -67,6 +67,118 @@ pub fn implicit_type_cast(
     return Ok(l_type.clone());
 }
 
+/// The type of a floating constant given by its suffix, C11 6.4.4.2p4.
+pub fn float_constant_type(suffix: lexer::FloatSuffix) -> symtable::TypeExpression {
+    match suffix {
+        lexer::FloatSuffix::None => symtable::TypeExpression::new_val(symtable::BaseType::Double),
+        lexer::FloatSuffix::F => symtable::TypeExpression::new_val(symtable::BaseType::Float),
+        lexer::FloatSuffix::L => {
+            let mut long_double = symtable::TypeExpression::new_val(symtable::BaseType::Long);
+            long_double.val.push(symtable::BaseType::Double);
+            long_double
+        }
+    }
+}
+
+/// The type of an integer constant: the first type of its list which can represent the
+/// value, C11 6.4.4.1p5.
+///
+/// ```
+/// use crust::symtable::BaseType::{Int, Long, Unsigned};
+/// use crust::{lexer, sema};
+///
+/// let type_of = |src: &str| match lexer::lex(src).unwrap()[0].kind {
+///     lexer::TokType::IConstant(value, suffix) => sema::integer_constant_type(value, suffix).val,
+///     _ => unreachable!(),
+/// };
+/// assert_eq!(type_of("2147483647"), [Int]);
+/// // a decimal constant skips the unsigned types, the others do not
+/// assert_eq!(type_of("2147483648"), [Long]);
+/// assert_eq!(type_of("0x80000000"), [Unsigned, Int]);
+/// assert_eq!(type_of("4294967295u"), [Unsigned, Int]);
+/// assert_eq!(type_of("42l"), [Long]);
+/// assert_eq!(type_of("0xffffffffffffffffLL"), [Unsigned, Long, Long]);
+/// ```
+pub fn integer_constant_type(value: u128, suffix: lexer::IntSuffix) -> symtable::TypeExpression {
+    use symtable::BaseType::{Int, Long, Unsigned};
+    use symtable::{X86_64_INT_BYTES, X86_64_LONG_BYTES, X86_64_LONG_LONG_BYTES};
+
+    // (type, is unsigned, size in bytes), the types of the list without suffix
+    let types: [(&[symtable::BaseType], bool, u64); 6] = [
+        (&[Int], false, X86_64_INT_BYTES),
+        (&[Unsigned, Int], true, X86_64_INT_BYTES),
+        (&[Long], false, X86_64_LONG_BYTES),
+        (&[Unsigned, Long], true, X86_64_LONG_BYTES),
+        (&[Long, Long], false, X86_64_LONG_LONG_BYTES),
+        (&[Unsigned, Long, Long], true, X86_64_LONG_LONG_BYTES),
+    ];
+    let first = match suffix.length {
+        lexer::IntLength::None => 0,
+        lexer::IntLength::Long => 2,
+        lexer::IntLength::LongLong => 4,
+    };
+    let (val, _, _) = types[first..]
+        .iter()
+        // a decimal constant is only unsigned with `u`
+        .filter(|(_, unsigned, _)| *unsigned || !suffix.unsigned)
+        .filter(|(_, unsigned, _)| !*unsigned || suffix.unsigned || !suffix.decimal)
+        .find(|(_, unsigned, bytes)| {
+            let bits = if *unsigned { bytes * 8 } else { bytes * 8 - 1 };
+            value < 1 << bits
+        })
+        // like gcc, a decimal constant too large for long long is unsigned long long
+        .unwrap_or(&types[5]);
+    symtable::TypeExpression {
+        val: val.to_vec(),
+        child: Vec::new(),
+    }
+}
+
+/// The type of the elements of a string literal, C11 6.4.5p6.
+fn element_type(encoding: lexer::Encoding) -> Vec<symtable::BaseType> {
+    use symtable::BaseType::{Char, Int, Short, Unsigned};
+    match encoding {
+        lexer::Encoding::Char | lexer::Encoding::Utf8 => vec![Char],
+        // the types of wchar_t, char16_t and char32_t, like the bundled headers
+        lexer::Encoding::Wide => vec![Int],
+        lexer::Encoding::Char16 => vec![Unsigned, Short],
+        lexer::Encoding::Char32 => vec![Unsigned, Int],
+    }
+}
+
+/// The type of a string literal of `len` code units, the null character is added.
+///
+/// ```
+/// use crust::symtable::BaseType::{Array, Short, Unsigned};
+/// use crust::{lexer, sema};
+///
+/// // a character outside of the basic multilingual plane takes two UTF-16 code units
+/// match &lexer::lex("u\"\\U0001F600\"").unwrap()[0].kind {
+///     lexer::TokType::StringLiteral(units, encoding, _) => assert_eq!(
+///         sema::string_literal_type(units.len(), *encoding).val,
+///         [Array(3), Unsigned, Short]
+///     ),
+///     _ => unreachable!(),
+/// }
+/// ```
+pub fn string_literal_type(len: usize, encoding: lexer::Encoding) -> symtable::TypeExpression {
+    let mut res = symtable::TypeExpression::new_val(symtable::BaseType::Array(len + 1));
+    res.val.extend(element_type(encoding));
+    res
+}
+
+/// The type of a character constant, int without prefix, C11 6.4.4.4p10.
+pub fn char_constant_type(encoding: lexer::Encoding) -> symtable::TypeExpression {
+    let val = match encoding {
+        lexer::Encoding::Char | lexer::Encoding::Utf8 => vec![symtable::BaseType::Int],
+        _ => element_type(encoding),
+    };
+    symtable::TypeExpression {
+        val,
+        child: Vec::new(),
+    }
+}
+
 /// Semantics analysis driver
 /// # Args:
 /// * `ParseNode` : root of the parse tree
@@ -15,10 +15,12 @@
 // symtable.rs: symbol table for identifiers.
 // ------------------------------------------------------------------------
 
-const _X86_64_CHAR_BYTES: u64 = 1;
-const _X86_64_SHORT_BYTES: u64 = 2;
-const X86_64_INT_BYTES: u64 = 4;
-const _X86_64_LONG_BYTES: u64 = 8;
+pub const X86_64_CHAR_BYTES: u64 = 1;
+pub const X86_64_SHORT_BYTES: u64 = 2;
+pub const X86_64_INT_BYTES: u64 = 4;
+pub const X86_64_LONG_BYTES: u64 = 8;
+pub const X86_64_LONG_LONG_BYTES: u64 = 8;
+pub const X86_64_POINTER_BYTES: u64 = 8;
 
 const NUM_REG: usize = 16;
 
@@ -0,0 +1,12 @@
+/* the -E output keeps apart the tokens which would be read back as other tokens,
+   the division by a dereference compiles but the double slash does not */
+#define SLASH /
+#define PLUS +
+#define MINUS -
+#define NAME x
+#define ONE 1e
+
+int x = 4 SLASH* &x;
+int y = 4 SLASH/ 2;
+int z = x PLUS+ 1 MINUS- 1 MINUS> 0;
+int w = NAME NAME ONE+1 NAME.1;
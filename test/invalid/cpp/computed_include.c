@@ -0,0 +1,6 @@
+#define HEADER 42
+#include HEADER
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,5 @@
+#define defined 1
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,7 @@
+#if 1
+#else
+#else
+#endif
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,4 @@
+#endif
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,7 @@
+#if 1
+#error crust stops here
+#endif
+
+int main(void) {
+    return 0;
+}
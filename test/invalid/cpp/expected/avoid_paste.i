@@ -0,0 +1,4 @@
+int x = 4 / * &x;
+int y = 4 / / 2;
+int z = x + + 1 - - 1 - > 0;
+int w = x x 1e +1 x.1;
@@ -0,0 +1,5 @@
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+
+int main(void) {
+    return MAX(1);
+}
@@ -0,0 +1,8 @@
+/* the error is reported with the chain of macro expansions */
+#define BAD @
+#define USE(x) ((x) + BAD)
+#define OUTER USE(1)
+
+int main(void) {
+    return OUTER;
+}
@@ -0,0 +1,5 @@
+/* a syntax error in a macro expansion is reported with the whole chain of expansions */
+#define TWICE(x) x x
+#define VALUE TWICE(1)
+
+int a = VALUE;
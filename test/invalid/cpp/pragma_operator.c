@@ -0,0 +1 @@
+_Pragma(pack) int x;
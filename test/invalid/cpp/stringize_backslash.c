@@ -0,0 +1,4 @@
+/* `#` must make a valid string literal, a `\` at the end of the argument breaks it */
+#define S(x) #x
+
+char *p = S(\);
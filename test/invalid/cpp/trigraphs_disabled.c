@@ -0,0 +1,4 @@
+// trigraphs are only replaced with -trigraphs
+int main(void) ??<
+    return 0;
+??>
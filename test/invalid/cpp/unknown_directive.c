@@ -0,0 +1,5 @@
+#defne A 1
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,4 @@
+#if 1
+int main(void) {
+    return 0;
+}
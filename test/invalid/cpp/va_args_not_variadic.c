@@ -0,0 +1,5 @@
+#define F(a) a + __VA_ARGS__
+
+int main(void) {
+    return F(1);
+}
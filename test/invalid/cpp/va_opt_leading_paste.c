@@ -0,0 +1,5 @@
+#define F(x, ...) __VA_OPT__(## x)
+
+int main(void) {
+    return F(1, 2);
+}
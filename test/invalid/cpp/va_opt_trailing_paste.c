@@ -0,0 +1,6 @@
+#define F(...) __VA_OPT__(a ##)
+
+int main(void) {
+    int a = 0;
+    return F(1);
+}
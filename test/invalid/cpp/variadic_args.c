@@ -0,0 +1,5 @@
+#define F(a, b, ...) a + b
+
+int main(void) {
+    return F(1);
+}
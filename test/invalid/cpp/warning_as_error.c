@@ -0,0 +1,6 @@
+#pragma GCC diagnostic error "-Wcpp"
+#warning this warning is an error
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,5 @@
+/* a hexadecimal floating constant needs a binary exponent */
+int main() {
+    double a = 0x1.8;
+    return 0;
+}
@@ -0,0 +1,4 @@
+/* no integer type can represent the value */
+int main() {
+    return 18446744073709551616;
+}
@@ -0,0 +1,4 @@
+/* the error is reported at the line and column of the token, which are
+   not moved by the comments and the line splices before it */
+int a = 1; /* one */ int \
+b = 2 /* two */ int c;
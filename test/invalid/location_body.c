@@ -0,0 +1,7 @@
+/* the error inside a function body is reported at its token, not at the `{` of the
+   function which fails to parse as a whole */
+int main(void) {
+    int a;
+    a = a +;
+    return 0;
+}
@@ -0,0 +1,4 @@
+/* multi-character constants are implementation-defined and not supported */
+int main() {
+    return 'ab';
+}
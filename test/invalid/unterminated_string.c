@@ -0,0 +1,5 @@
+/* a string literal ends on the line where it starts */
+int main() {
+    char *s = "abc;
+    return 0;
+}
@@ -0,0 +1,11 @@
+int main() {
+    char *a = "tab\t, quote \", backslash \\ and newline\n";
+    char *b = "octal \101\7, hex \x41 and " "concatenated \x12" "3";
+    char *c = u8"UTF-8 \u00e9";
+    int *d = L"wide " "string";
+    unsigned short *e = u"UTF-16 \U0001F600";
+    unsigned int *f = U"UTF-32 \u00e9";
+    int g = 'a' + '\'' + '\n' + '\0' + '\x7f' + '\377';
+    int h = L'\u00e9' + u'\xffff' + U'\U0001F600';
+    return 0;
+}
@@ -0,0 +1,15 @@
+#include <stdarg.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#if INT8_MIN != -128 || UINT16_MAX != 65535 || INT_FAST16_MAX != INT64_MAX
+#error "wrong stdint limits"
+#endif
+#if SIZE_MAX != UINTPTR_MAX || PTRDIFF_MAX != INTPTR_MAX || WCHAR_MIN != -2147483647 - 1
+#error "wrong stddef limits"
+#endif
+
+size_t size = offsetof(struct { char c; int i; }, i);
+int64_t big = INT64_C(1) << 40;
+uint32_t small = UINT32_C(7);
+va_list ap;
@@ -0,0 +1,8 @@
+typedef long unsigned int size_t;
+typedef long int int64_t;
+typedef unsigned int uint32_t;
+typedef __builtin_va_list __gnuc_va_list;
+typedef __gnuc_va_list va_list;
+size_t size = ((long unsigned int)&((struct { char c; int i; } *)0)->i);
+int64_t big = 1L << 40;
+uint32_t small = 7U;
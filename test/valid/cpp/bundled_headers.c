@@ -0,0 +1,37 @@
+#include <float.h>
+#include <iso646.h>
+#include <limits.h>
+#include <stdalign.h>
+#include <stdbool.h>
+#include <stdnoreturn.h>
+
+// typedefs and casts are not parsed yet, the other headers are tested in bundled/
+#define __need_NULL
+#include <stddef.h>
+
+#if CHAR_BIT != 8 || SCHAR_MIN != -128 || UCHAR_MAX != 255
+#error "wrong char limits"
+#endif
+#if SHRT_MAX != 32767 || INT_MIN != -2147483647 - 1 || UINT_MAX != 4294967295U
+#error "wrong int limits"
+#endif
+#if LONG_MAX != 9223372036854775807 || ULLONG_MAX != 18446744073709551615U
+#error "wrong long limits"
+#endif
+#if FLT_RADIX != 2 || DBL_MANT_DIG != 53 || LDBL_MANT_DIG != 64
+#error "wrong float characteristics"
+#endif
+#if !__bool_true_false_are_defined || !__alignas_is_defined
+#error "stdbool.h and stdalign.h are not complete"
+#endif
+#if !defined(NULL) || defined(offsetof)
+#error "__need_NULL only asks for NULL"
+#endif
+
+noreturn int stop(void);
+
+int main(void) {
+    alignas(4) int x = 1 bitor 2;
+    bool done = true;
+    return done and x not_eq 3;
+}
@@ -0,0 +1,10 @@
+#include <stdarg.h>
+
+// va_list is left out when crust compiles, the macros are still defined
+#if !defined(va_start) || !defined(va_arg) || !defined(va_end) || !defined(va_copy)
+#error "stdarg.h is not complete"
+#endif
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,10 @@
+#include <stddef.h>
+
+// the typedefs are left out when crust compiles, the macros are still defined
+#if !defined(NULL) || !defined(offsetof)
+#error "stddef.h is not complete"
+#endif
+
+int main(void) {
+    return 0;
+}
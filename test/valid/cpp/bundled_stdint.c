@@ -0,0 +1,11 @@
+#include <stdint.h>
+
+// the typedefs are left out when crust compiles, the limits and the constants work
+#if INT8_MIN != -128 || UINT16_MAX != 65535 || INTMAX_MAX != INT64_MAX
+#error "wrong stdint limits"
+#endif
+
+int main(void) {
+    long big = INT64_C(1) << 40;
+    return big != 1099511627776 || UINT32_C(7) != 7 || INT8_MIN != -128;
+}
@@ -0,0 +1,4 @@
+#ifndef LEVEL
+#error "-include is processed after -D"
+#endif
+#define FROM_CONFIG LEVEL
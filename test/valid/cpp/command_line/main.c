@@ -0,0 +1,11 @@
+/* built with -DDEBUG -DLEVEL=2 -DREMOVED -UREMOVED -D'TWICE(x)=((x)*2)' -include config.h */
+#ifndef DEBUG
+#error "DEBUG should be defined by -DDEBUG"
+#endif
+#ifdef REMOVED
+#error "REMOVED should be undefined by -UREMOVED"
+#endif
+
+int main(void) {
+    return DEBUG + TWICE(LEVEL) + FROM_CONFIG;
+}
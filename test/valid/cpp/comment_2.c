@@ -0,0 +1,16 @@
+#define URL "http://example.com/*not a comment*/"
+#define SLASH '/'
+#define ONE /* a comment which
+               spans lines */ 1
+#define TWO \
+    2
+
+int main(void) {
+    printf("http://crust"); // a comment after a "string"
+    printf(URL);
+    /* /* the inner start of a comment gives a warning */
+    char quote = '"'; /* '"' */
+    char slash = SLASH; // '/'
+    printf("ünïcödé // is not a comment, /* neither is this */");
+    return ONE + TWO + quote + slash + '\'';
+}
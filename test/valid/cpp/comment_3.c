@@ -0,0 +1,3 @@
+int x; /* a /* nested comment */
+int main(void) { return 0; }
+\
\ No newline at end of file
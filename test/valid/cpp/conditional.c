@@ -0,0 +1,58 @@
+#define FOO 1
+#define BAR(x) ((x) * 2)
+#define EMPTY
+
+#ifndef GUARD_H
+#define GUARD_H
+int guarded = 1;
+#endif
+
+#ifndef GUARD_H
+#error "the guard should have been defined"
+#endif
+
+#if defined(FOO) && defined BAR && !defined(BAZ)
+int a = 1;
+#elif 1
+#error "#elif after a taken group should be skipped"
+#else
+#error "#else after a taken group should be skipped"
+#endif
+
+#define HAS_FOO defined(FOO)
+#define HAS(x) defined x
+#if !HAS_FOO || HAS(BAZ) || !HAS(BAR)
+#error "defined produced by a macro should be answered"
+#endif
+
+#if BAR(FOO) == 2 && (FOO ? 3 : 4) == 3 && UNDEFINED_MACRO == 0
+int b = 2;
+#endif
+
+#if -1 > 0u && 0x10 == 16 && 010 == 8 && 'a' == 97 && '\377' < 0
+int c = 3;
+#endif
+
+#if 0
+#if this is skipped and never evaluated
+#else
+#endif
+#elif 2
+int d = 4;
+#elif 1 / 0
+#else
+#endif
+
+#if 0 && (1 / 0) || 1 || (1 % 0)
+int e = 5;
+#endif
+
+#if (2 + 3 * 4 - 6 / 2 % 4 << 1 >> 1 | 1 ^ 2 & 3) == 11 && ~0 == -1
+int f = 6;
+#endif
+
+#ifdef EMPTY
+int main(void) {
+    return a + b + c + d + e + f + guarded;
+}
+#endif
@@ -0,0 +1,12 @@
+%:include "header1.h"
+%:define CAT(a, b) a %:%: b
+%:define STR(x) %:x
+
+// digraphs are tokens, so they are not replaced in literals: "<:" — naïve ünïcödé
+char *spelling = STR(<: %>);
+int CAT(array, 1) <:2:> = <% 1, 2 %>;
+
+int main(void) <%
+    char *s = "<% é %>";
+    return array1<:0:> + (sizeof spelling) - 8;
+%>
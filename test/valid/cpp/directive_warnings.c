@@ -0,0 +1,17 @@
+/* the warnings of the directives and of the pragmas point at the directive name */
+#define A 1
+#ifdef A extra
+#endif
+#undef A extra
+#undef __LINE__
+#pragma once
+#pragma pack[4]
+#pragma STDC FP_CONTRACT MAYBE
+_Pragma("STDC UNKNOWN")
+#pragma GCC diagnostic
+#line 13 "directive_warnings.c" extra
+#line 0
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,35 @@
+/* #undef, redefinitions, #warning, the null directive, #ident and #pragma */
+#define A 1
+#undef A
+#ifdef A
+#error "A should be undefined"
+#endif
+#undef NEVER_DEFINED
+
+#define B (1 + 2)
+#define B (1 + 2)   /* identical redefinition, no diagnostic */
+#define F(x) ((x) * 2)
+#define F(x) ((x)  *  2)
+#undef B
+#define B 3
+#define C 1
+#define C 2             /* "C" redefined */
+
+#
+#ident "crust test"
+#warning this is only a warning
+#pragma pack(push, 4)
+#pragma pack(pop)
+#pragma pack()
+#pragma GCC diagnostic push
+#pragma GCC diagnostic ignored "-Wunused"
+#pragma GCC diagnostic ignored "-Wcpp"
+#warning this warning is ignored
+#pragma GCC diagnostic pop
+#warning this one is shown again
+#pragma STDC FP_CONTRACT ON
+#pragma unknown_pragma is ignored
+
+int main(void) {
+    return F(B);
+}
@@ -0,0 +1,10 @@
+int global = 1;
+int main(void) {
+int a = ((1) > (2) ? (1) : (2));
+int b = (((a) > (3) ? (a) : (3)) + (4, 5));
+int c = ((a) > (b) ? (a) : (b));
+printf("hello 'c' world");
+printf("12");
+printf("spaces between");
+return a + b + MAX;
+}
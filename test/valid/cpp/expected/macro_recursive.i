@@ -0,0 +1,8 @@
+int foo = 1;
+int b = 2;
+int main(void) {
+int x = a + foo;
+x = 2 * 9 * g;
+x = 42;
+return x;
+}
@@ -0,0 +1,10 @@
+int j[] = { 123, 45, 67, 89,
+10, 11, 12, 0 };
+int main(void) {
+printf("x" "1" "= %d, x" "2" "= %s", x1, x2);
+fputs("strncmp(\"abc\\0d\", \"abc\", '\\4') == 0" ": @\n", s);
+printf("vers2.h");
+"hello";
+"hello" ", world";
+return 0;
+}
@@ -0,0 +1,6 @@
+#ifndef NOT_GUARD_H
+#define NOT_GUARD_H
+int not_guard;
+#else
+#define NOT_GUARD_TWICE 3
+#endif
@@ -0,0 +1,5 @@
+#pragma once
+#ifdef ONCE
+#error "once.h is included twice"
+#endif
+#define ONCE 1
@@ -0,0 +1,14 @@
+#include "header/once.h"
+#include "header/once.h"
+#include "header/../header/once.h"
+#include "header/guard.h"
+#include "header/guard.h"
+#include "header/not_guard.h"
+#include "header/not_guard.h"
+#ifndef NOT_GUARD_TWICE
+#error "a file with #else is not guarded"
+#endif
+
+int main(void) {
+    return ONCE + GUARD + NOT_GUARD_TWICE + not_guard;
+}
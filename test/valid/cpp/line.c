@@ -0,0 +1,18 @@
+#line 100
+#if __LINE__ != 100
+#error "#line sets the number of the next line"
+#endif
+#define LINE 20
+#line LINE "renamed.c"
+int line = __LINE__;
+# 1 "header.h" 1
+int in_header;
+# 22 "renamed.c" 2
+
+int main(void) {
+#if __LINE__ != 24
+#error "linemarkers set the line number"
+#endif
+    printf("%s\n", __FILE__);
+    return line + in_header;
+}
@@ -0,0 +1,21 @@
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+#define STR(x) #x
+#define XSTR(x) STR(x)
+#define CAT(a, b) a##b
+#define EMPTY()
+#define ADD(x, y) (x + y)
+#define APPLY(f, x, y) f(x, y)
+
+int CAT(glo, bal) = 1;
+
+int main(void) {
+    int a = MAX(1, 2);
+    int b = ADD(MAX(a, 3),
+                (4, 5));
+    int c = APPLY(MAX, a, b);
+    printf(STR(hello 'c' world));
+    printf(XSTR(CAT(1, 2)));
+    printf(STR(  spaces   between  ));
+    EMPTY()
+    return CAT(a, ) + CAT(, b) + MAX;
+}
@@ -0,0 +1,11 @@
+#define x 42
+#define STR "x is not replaced in a string"
+#define FIRST(a) ((a)[0])
+
+int arr[x];
+
+int main(void) {
+    printf("x");
+    printf(STR);
+    return x + 'x' + FIRST(arr);
+}
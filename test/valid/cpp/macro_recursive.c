@@ -0,0 +1,18 @@
+#define foo foo
+#define a b
+#define b a
+#define f(a) a * g
+#define g(a) f(a)
+#define NIL(xxx) xxx
+#define G_0(arg) NIL(G_1)(arg)
+#define G_1(arg) NIL(arg)
+
+int foo = 1;
+int b = 2;
+
+int main(void) {
+    int x = a + foo;
+    x = f(2)(9);
+    x = G_0(42);
+    return x;
+}
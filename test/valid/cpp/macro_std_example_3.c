@@ -0,0 +1,23 @@
+// C11 6.10.3.5 EXAMPLE 3
+#define x 2
+#define f(a) f(x * (a))
+#define g f
+#define z z[0]
+#define h g(~
+#define m(a) a(w)
+#define w 0,1
+#define t(a) a
+#define p() int
+#define q(x) x
+#define r(x,y) x ## y
+#define str(x) # x
+
+int main(void) {
+    f(y+1) + f(f(z)) % t(t(g)(0) + t)(1);
+    g(x+(3,4)-w) | h 5) & m
+        (f)^m(m);
+    // the parser does not accept a trailing comma in initializers, so `0` follows `r(,)`
+    p() i[q()] = { q(1), r(2,3), r(4,), r(,5), r(,) 0 };
+    char c[2][6] = { str(hello), str() };
+    return 0;
+}
@@ -0,0 +1,25 @@
+// C11 6.10.3.5 EXAMPLE 4 and EXAMPLE 5
+#define str(s) # s
+#define xstr(s) str(s)
+#define debug(s, t) printf("x" # s "= %d, x" # t "= %s", \
+                           x ## s, x ## t)
+#define INCFILE(n) vers ## n
+#define glue(a, b) a ## b
+#define xglue(a, b) glue(a, b)
+#define HIGHLOW "hello"
+#define LOW LOW ", world"
+#define t(x,y,z) x ## y ## z
+
+// the parser does not accept a trailing comma in initializers, so `0` follows `t(,,)`
+int j[] = { t(1,2,3), t(,4,5), t(6,,7), t(8,9,),
+            t(10,,), t(,11,), t(,,12), t(,,) 0 };
+
+int main(void) {
+    debug(1, 2);
+    fputs(str(strncmp("abc\0d", "abc", '\4') // this goes away
+              == 0) str(: @\n), s);
+    printf(xstr(INCFILE(2).h));
+    glue(HIGH, LOW);
+    xglue(HIGH, LOW);
+    return 0;
+}
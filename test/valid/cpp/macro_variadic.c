@@ -0,0 +1,23 @@
+#define LOG(fmt, ...) printf(fmt, __VA_ARGS__)
+#define GNU_LOG(fmt, ...) printf(fmt, ## __VA_ARGS__)
+#define NAMED_LOG(fmt, args...) printf(fmt, ## args)
+#define OPT_LOG(fmt, ...) printf(fmt __VA_OPT__(,) __VA_ARGS__)
+#define COUNT(...) count(0 __VA_OPT__(+ 1), #__VA_ARGS__)
+#define FIRST(first, ...) first
+#define CALL(f, ...) f(__VA_ARGS__)
+#define ONE 1
+
+int main(void) {
+    int a = 1;
+    LOG("%d %d\n", a, (a, 2));
+    GNU_LOG("hello\n");
+    GNU_LOG("%d\n", ONE);
+    NAMED_LOG("hello\n");
+    NAMED_LOG("%d %d\n", a, a);
+    OPT_LOG("hello\n");
+    OPT_LOG("%d\n", a);
+    COUNT();
+    COUNT(a, b);
+    a = FIRST(ONE, 2, 3) + FIRST(a);
+    return CALL(FIRST, a, ONE);
+}
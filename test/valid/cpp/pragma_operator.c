@@ -0,0 +1,32 @@
+/* the _Pragma operator and the __has_* queries */
+#define DO_PRAGMA(x) _Pragma(#x)
+#define PACK(n) DO_PRAGMA(pack(n))
+#define DIAGNOSTIC(kind, option) DO_PRAGMA(GCC diagnostic kind option)
+
+PACK(4)
+int packed;
+_Pragma("pack()") int unpacked;
+DIAGNOSTIC(ignored, "-Wunused")
+_Pragma("message(\"a \\\"quoted\\\" message\")")
+
+#if !defined(__has_builtin) || !defined __has_feature
+#error "the __has_* operators count as defined macros"
+#endif
+#if __has_builtin(__builtin_unknown) || __has_attribute(__unknown__)
+#error "crust has no such builtin or attribute"
+#endif
+#if defined(__STDC_NO_ATOMICS__) && __has_feature(c_atomic)
+#error "__has_feature(c_atomic) contradicts __STDC_NO_ATOMICS__"
+#endif
+
+/* the operators also work when a macro expands to them, like in portability headers */
+#define HAS_BUILTIN(x) __has_builtin(x)
+#define HAS_FEATURE(x) (__has_feature(x) || __has_extension(x))
+#if !HAS_BUILTIN(__builtin_va_start) || !HAS_BUILTIN(__builtin_va_arg) || !HAS_BUILTIN(__builtin_va_end)
+#error "the builtins of stdarg.h are missing"
+#endif
+#if __has_feature(c_static_assert) && HAS_FEATURE(c_generic_selections)
+int main(void) {
+    return 0;
+}
+#endif
@@ -0,0 +1,25 @@
+#if !defined(__STDC__) || __STDC_VERSION__ < 201112L
+#error "crust defaults to C11"
+#endif
+#if !defined(__x86_64__) || !defined(__linux__) || !__LP64__
+#error "crust targets x86_64 linux"
+#endif
+#if __SIZEOF_LONG__ != 8 || __CHAR_BIT__ != 8
+#error "unexpected type sizes"
+#endif
+#define HERE __LINE__
+#define UNIQUE(name) CAT(name, __COUNTER__)
+#define CAT(a, b) XCAT(a, b)
+#define XCAT(a, b) a##b
+
+int UNIQUE(var) = __COUNTER__;
+
+int main(void) {
+    int line = HERE;
+    int UNIQUE(tmp) = __LINE__;
+    printf("%s %d %s %s\n", __FILE__, line, __DATE__, __TIME__);
+#if __LINE__ != 21
+#error "__LINE__ is wrong"
+#endif
+    return var0 + tmp2;
+}
@@ -0,0 +1,2 @@
+#include "sub/sub.h"
+int angled_value = 1;
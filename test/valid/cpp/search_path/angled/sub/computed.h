@@ -0,0 +1 @@
+int computed_value = 10;
@@ -0,0 +1,6 @@
+/* a wrapper header, which adds to the header of the same name further down the search path */
+#if !__has_include_next(<wrapped.h>)
+#error "the next wrapped.h is in the system directory"
+#endif
+#include_next <wrapped.h>
+int wrapper_value = 8;
@@ -0,0 +1,2 @@
+#include "sibling.h"
+int local_value = 5;
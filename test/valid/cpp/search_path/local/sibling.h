@@ -0,0 +1 @@
+int sibling_value = 6;
@@ -0,0 +1,30 @@
+#include <angled.h>
+#include <sys_header.h>
+#include "quote.h"
+#include "local/local.h"
+#include <wrapped.h>
+
+#ifndef __has_include
+#error "__has_include counts as a defined macro"
+#endif
+#if !__has_include(<sys_header.h>) || __has_include("missing.h")
+#error "__has_include should find the headers like #include"
+#endif
+/* __has_include produced by a macro, the operand is not a header-name token then */
+#define HAS_INCLUDE(x) __has_include(x)
+#if !HAS_INCLUDE(<stdbool.h>) || !HAS_INCLUDE(<sub/computed.h>) || HAS_INCLUDE("missing.h")
+#error "__has_include should work through a macro"
+#endif
+
+/* computed includes */
+#define CONFIG_HEADER "config.h"
+#if __has_include(CONFIG_HEADER)
+#include CONFIG_HEADER
+#endif
+#define ANGLED(name) <name.h>
+#include ANGLED(sub/computed)
+
+int main(void) {
+    return angled_value + sub_value + sys_value + quote_value + local_value + sibling_value
+        + wrapped_value + wrapper_value + config_value + computed_value;
+}
@@ -0,0 +1 @@
+int config_value = 9;
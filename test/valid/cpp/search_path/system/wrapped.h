@@ -0,0 +1 @@
+int wrapped_value = 7;
@@ -0,0 +1,10 @@
+#if __STDC_VERSION__ != 199901L || defined(__STDC_NO_VLA__)
+#error "-std=c99 should select C99"
+#endif
+#ifdef linux
+#error "linux is only defined in the GNU dialects"
+#endif
+
+int main(void) {
+    return 0;
+}
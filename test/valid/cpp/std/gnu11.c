@@ -0,0 +1,10 @@
+#if __STDC_VERSION__ != 201112L || !defined(__STDC_NO_VLA__)
+#error "-std=gnu11 should select C11"
+#endif
+#if !defined(linux) || !defined(unix) || defined(__STRICT_ANSI__)
+#error "the GNU dialect defines linux and unix"
+#endif
+
+int main(void) {
+    return 0;
+}
@@ -0,0 +1,18 @@
+int main() {
+    double a = 3.14;
+    double b = 1.;
+    double c = .5;
+    double d = 1e-3;
+    double e = 2.5E+10;
+    float f = 0.5f;
+    long double g = 1.5L;
+    double h = 0x1.8p3;
+    float i = 0X.8P-1F;
+    double j = 1.7976931348623157e308;
+    double k = 0x1p-1074;
+    double l = 0x1.fffffffffffff8p0;
+    // out of range, infinite with a warning like gcc
+    long double m = 1e400L;
+    float n = 0x1p128f;
+    return 0;
+}
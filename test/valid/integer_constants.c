@@ -0,0 +1,16 @@
+int main() {
+    int a = 42;
+    int b = 0x2a;
+    int c = 0X2A;
+    int d = 052;
+    int e = 0b101010;
+    unsigned int f = 42u;
+    long g = 42L;
+    unsigned long h = 42ul + 42LU;
+    long long i = 42ll;
+    unsigned long long j = 42ULL + 42llu;
+    unsigned long long k = 18446744073709551615u;
+    long l = 0x7fffffffffffffff;
+    long m = 2147483648 + 0xffffffff + 037777777777 + 0b11u;
+    return 0;
+}
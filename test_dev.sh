@@ -33,6 +33,39 @@ do
     fi
 done
 
+# the constants and literals are lexed to the expected values, suffixes and encodings
+srcdir=test/valid
+tmpfile=$(mktemp)
+for f in $srcdir/expected/*.tok
+do
+    inc=$(($inc+1))
+    name=$(basename ${f%.*})
+    echo "TEST $inc: tokens of [$srcdir/$name]"
+    ./target/debug/crust --crust-print-source-token $srcdir/$name.c -o /dev/null > $tmpfile && \
+        diff $f $tmpfile
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+rm $tmpfile
+
+# the floating constants out of range are infinite with gcc's warning
+srcdir=test/valid
+inc=$(($inc+1))
+echo "TEST $inc: warnings of [$srcdir/float_constants]"
+warnings=$(./target/debug/crust $srcdir/float_constants.c -o /dev/null 2>&1)
+echo "$warnings" | grep -qF "$srcdir/float_constants.c:15:21: floating constant exceeds range of 'long double' [-Woverflow]" && \
+    echo "$warnings" | grep -qF "$srcdir/float_constants.c:16:15: floating constant exceeds range of 'float' [-Woverflow]"
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+
 srcdir=sample_code
 for f in $srcdir/*.c
 do
@@ -62,6 +95,137 @@ do
     fi
 done
 
+# should cause error in preprocessor
+srcdir=test/invalid/cpp
+for f in $srcdir/*.c
+do
+    inc=$(($inc+1))
+    file=${f%.*}
+    crust_compile $file ./gen/$file
+    if [ "$?" -ne 1 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+
+# the error points at the line and column of the token in the source file
+srcdir=test/invalid
+for expected in "location.c:4:17: Expected \`;\`, found \`int\`" \
+    "location_body.c:5:12: "
+do
+    inc=$(($inc+1))
+    file=${expected%%:*}
+    echo "TEST $inc: location of the error in [$srcdir/${file%.*}]"
+    ./target/debug/crust $srcdir/$file -o /dev/null 2>&1 | grep -qF "$srcdir/$expected"
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+
+# the preprocessor errors point at the directive or the macro name
+srcdir=test/invalid/cpp
+for expected in "else_after_else.c:3:2: #else after #else" \
+    "unterminated_if.c:1:2: unterminated #if" \
+    "div_zero.c:1:2: division by zero in #if" \
+    "macro_args.c:4:12: macro" \
+    "missing_header.c:1:2: no_such_header.h: No such file or directory" \
+    "error_directive.c:2:2: #error crust stops here" \
+    "warning_as_error.c:2:2: #warning this warning is an error [-Werror=cpp]" \
+    "stringize_backslash.c:4:11: invalid string literal: trailing \`\\\`"
+do
+    inc=$(($inc+1))
+    file=${expected%%:*}
+    echo "TEST $inc: location of the error in [$srcdir/${file%.*}]"
+    ./target/debug/crust -E $srcdir/$file -o /dev/null 2>&1 | \
+        grep -qF "$srcdir/$expected"
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+
+# the errors in a macro expansion are followed by the chain of expansions
+srcdir=test/invalid/cpp
+tmpfile=$(mktemp)
+for f in $srcdir/expected/*.err
+do
+    inc=$(($inc+1))
+    name=$(basename ${f%.*})
+    echo "TEST $inc: macro expansion notes of [$srcdir/$name]"
+    ./target/debug/crust $srcdir/$name.c -o /dev/null 2> $tmpfile
+    diff $f $tmpfile
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+rm $tmpfile
+
+# the warnings point at the directive and #pragma GCC diagnostic can ignore them
+srcdir=test/valid/cpp
+inc=$(($inc+1))
+echo "TEST $inc: warnings of [$srcdir/directives]"
+warnings=$(./target/debug/crust -E $srcdir/directives.c -o /dev/null 2>&1)
+echo "$warnings" | grep -qF "$srcdir/directives.c:16:9: \"C\" redefined" && \
+    echo "$warnings" | grep -qF "$srcdir/directives.c:20:2: #warning this is only a warning [-Wcpp]" && \
+    echo "$warnings" | grep -qF "$srcdir/directives.c:29:2: #warning this one is shown again" && \
+    ! echo "$warnings" | grep -qF "is ignored"
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+
+# the warnings of the other directives and of the pragmas point at the directive name
+srcdir=test/valid/cpp
+inc=$(($inc+1))
+echo "TEST $inc: warnings of [$srcdir/directive_warnings]"
+warnings=$(./target/debug/crust -E $srcdir/directive_warnings.c -o /dev/null 2>&1)
+missing=0
+for expected in "$srcdir/directive_warnings.c:3:2: extra tokens at end of #ifdef directive" \
+    "$srcdir/directive_warnings.c:5:2: extra tokens at end of #undef directive" \
+    "$srcdir/directive_warnings.c:6:2: undefining \"__LINE__\"" \
+    "$srcdir/directive_warnings.c:7:2: #pragma once in main file" \
+    "$srcdir/directive_warnings.c:8:2: malformed '#pragma pack' - ignored" \
+    "$srcdir/directive_warnings.c:9:2: expected 'ON', 'OFF' or 'DEFAULT' in STDC pragma" \
+    "$srcdir/directive_warnings.c:10:1: unknown STDC pragma" \
+    "$srcdir/directive_warnings.c:11:2: expected [error|warning|ignored|push|pop]" \
+    "$srcdir/directive_warnings.c:12:2: extra tokens at end of #line directive" \
+    "directive_warnings.c:13:2: line number out of range"
+do
+    echo "$warnings" | grep -qF "$expected" || missing=1
+done
+if [ "$missing" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+
+# the warnings of the comments and the line splices point at their location
+srcdir=test/valid/cpp
+inc=$(($inc+1))
+echo "TEST $inc: warnings of [$srcdir/comment_3]"
+warnings=$(./target/debug/crust -E $srcdir/comment_3.c -o /dev/null 2>&1)
+echo "$warnings" | grep -qF "$srcdir/comment_3.c:1:13: \"/*\" within comment" && \
+    echo "$warnings" | grep -qF "$srcdir/comment_3.c:3:1: backslash-newline at end of file"
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+
 # should cause no error
 srcdir=test/valid/parser
 for f in $srcdir/*.c
@@ -92,4 +256,191 @@ do
     fi
 done
 
+# test for include search paths
+srcdir=test/valid/cpp/search_path
+inc=$(($inc+1))
+echo "TEST $inc: parse [$srcdir/main] with -I, -iquote and -isystem"
+./target/debug/crust -I $srcdir/angled -iquote $srcdir/quote -isystem $srcdir/system \
+    $srcdir/main.c -o ./gen/$srcdir/main.s
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+
+# test for -D, -U and -include
+srcdir=test/valid/cpp/command_line
+inc=$(($inc+1))
+echo "TEST $inc: parse [$srcdir/main] with -D, -U and -include"
+./target/debug/crust -DDEBUG -DLEVEL=2 -DREMOVED -UREMOVED -D'TWICE(x)=((x)*2)' \
+    -include $srcdir/config.h $srcdir/main.c -o ./gen/$srcdir/main.s
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+
+# test for dependency files: the make rule of -MM -MP matches the expected one
+srcdir=test/valid/cpp
+inc=$(($inc+1))
+echo "TEST $inc: make rule of [$srcdir/header1] with -MM -MP"
+tmpfile=$(mktemp)
+./target/debug/crust -MM -MP $srcdir/header1.c -o $tmpfile && diff $srcdir/deps/header1.d $tmpfile
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+rm $tmpfile
+
+# the bundled headers are not in the make rule, even with -M
+srcdir=test/valid/cpp
+inc=$(($inc+1))
+echo "TEST $inc: make rule of [$srcdir/bundled/types] with -M"
+tmpfile=$(mktemp)
+./target/debug/crust -M $srcdir/bundled/types.c -o $tmpfile && diff $srcdir/deps/types.d $tmpfile
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+rm $tmpfile
+
+# test for macro expansion: the -E -P output matches the expected one, also for the
+# files in test/invalid/cpp which only fail to compile after preprocessing
+tmpfile=$(mktemp)
+for f in test/valid/cpp/expected/*.i test/invalid/cpp/expected/*.i
+do
+    inc=$(($inc+1))
+    srcdir=$(dirname $(dirname $f))
+    name=$(basename ${f%.*})
+    echo "TEST $inc: expand the macros of [$srcdir/$name] with -E -P"
+    ./target/debug/crust -E -P $srcdir/$name.c -o $tmpfile && diff $f $tmpfile
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+rm $tmpfile
+
+# test for #pragma once and the include guards: the linemarkers of -E show that the
+# skipped headers are not entered again
+srcdir=test/valid/cpp
+inc=$(($inc+1))
+echo "TEST $inc: headers entered by [$srcdir/include_once]"
+tmpfile=$(mktemp)
+./target/debug/crust -E $srcdir/include_once.c -o $tmpfile && \
+    entered=$(grep '^# 1 ".*" 1$' $tmpfile | cut -d'"' -f2 | tr '\n' ' ') && \
+    [ "$entered" = "$srcdir/header/once.h $srcdir/header/guard.h $srcdir/header/not_guard.h $srcdir/header/not_guard.h " ]
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+rm $tmpfile
+
+# test for -dM and -dD: the macros are dumped and the output can be compiled again
+srcdir=test/valid/cpp
+inc=$(($inc+1))
+echo "TEST $inc: dump the macros of [$srcdir/macro_function] with -dM and -dD"
+tmpdir=$(mktemp -d)
+./target/debug/crust -E -dM $srcdir/macro_function.c -o $tmpdir/macros.h && \
+    grep -q "^#define __STDC_VERSION__ 201112L$" $tmpdir/macros.h && \
+    ./target/debug/crust -E -dD $srcdir/macro_function.c -o $tmpdir/macro_function.i && \
+    ./target/debug/crust $tmpdir/macro_function.i -o ./gen/$srcdir/macro_function.s
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+rm -r $tmpdir
+
+# test for the bundled headers: each line of the expected output is in the -E output.
+# types.c is only preprocessed, the parser does not handle the typedefs of stdint.h,
+# stddef.h and stdarg.h yet and they are left out when crust compiles, see bundled_*.c
+srcdir=test/valid/cpp/bundled
+inc=$(($inc+1))
+echo "TEST $inc: expand the bundled headers in [$srcdir/types]"
+tmpfile=$(mktemp)
+contains_lines() {
+    while read -r line; do
+        grep -qxF "$line" $2 || return 1
+    done < $1
+}
+./target/debug/crust -E -P $srcdir/types.c -o $tmpfile && contains_lines $srcdir/types.i $tmpfile
+if [ "$?" -ne 0 ]; then
+    echo -e "[${RED}Error${NC}]"
+    exit 1
+else
+    echo -e "[${BLUE}parse ok${NC}]"
+fi
+rm $tmpfile
+
+# test for -std, like gcc the last one is used
+srcdir=test/valid/cpp/std
+for f in $srcdir/*.c
+do
+    inc=$(($inc+1))
+    file=${f%.*}
+    std=$(basename $file)
+    echo "TEST $inc: parse [$file] with -std=c89 -std=$std"
+    ./target/debug/crust -std=c89 -std=$std $file.c -o ./gen/$file.s
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+
+# test for -trigraphs: the trigraphs are replaced in the source and in the -E output
+srcdir=test/valid/cpp/trigraphs
+tmpdir=$(mktemp -d)
+for f in $srcdir/*.c
+do
+    inc=$(($inc+1))
+    file=${f%.*}
+    name=$(basename $file)
+    echo "TEST $inc: parse [$file] with -trigraphs"
+    ./target/debug/crust -trigraphs $file.c -o ./gen/$file.s && \
+        ./target/debug/crust -E -trigraphs $file.c -o $tmpdir/$name.i && \
+        ./target/debug/crust $tmpdir/$name.i -o ./gen/$file.s
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+rm -r $tmpdir
+
+# test for linemarkers: the output of -E can be compiled again, without the typedefs of the
+# bundled headers as when crust compiles
+srcdir=test/valid/cpp
+tmpdir=$(mktemp -d)
+for f in $srcdir/*.c
+do
+    inc=$(($inc+1))
+    file=${f%.*}
+    name=$(basename $file)
+    echo "TEST $inc: parse the -E output of [$file]"
+    ./target/debug/crust -E -D__CRUST_NO_TYPEDEF $file.c -o $tmpdir/$name.i && \
+        ./target/debug/crust $tmpdir/$name.i -o ./gen/$file.s
+    if [ "$?" -ne 0 ]; then
+        echo -e "[${RED}Error${NC}]"
+        exit 1
+    else
+        echo -e "[${BLUE}parse ok${NC}]"
+    fi
+done
+rm -r $tmpdir
+
 echo -e "Now the parser can parse them all"